@@ -0,0 +1,1158 @@
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use anyhow::bail;
+use dashmap::DashMap;
+use serde::Serialize;
+
+use crate::transform::{
+    build_internal_graph,
+    invert_internal_graph,
+    is_internal,
+    owning_package,
+    package_prefix,
+    sorted_edges,
+};
+use crate::tree::root_packages;
+
+// Function to collect, per scanned package, the distinct external libraries
+// (the first two segments of each external import) it depends on
+pub fn collect_external_libraries(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> HashMap<String, BTreeSet<String>> {
+    let internal_packages: HashSet<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+
+    imports_map
+        .iter()
+        .map(|entry| {
+            let libraries = entry
+                .value()
+                .iter()
+                .filter(|import_value| !is_internal(import_value, &internal_packages))
+                .map(|import_value| package_prefix(import_value, 2).to_string())
+                .collect();
+
+            (entry.key().to_string(), libraries)
+        })
+        .collect()
+}
+
+// Function to count how many imports point outside the scanned packages, tallied
+// by namespace root (the first `segments` segments of each import); the internal
+// import count is returned alongside, and the tally is sorted by count, then name
+pub fn tally_external_imports(
+    imports_map: &DashMap<String, Vec<String>>,
+    segments: usize,
+) -> (usize, Vec<(String, usize)>) {
+    let internal_packages: HashSet<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+
+    let mut internal_count = 0;
+    let mut tally = HashMap::<String, usize>::new();
+
+    for entry in imports_map.iter() {
+        for import_value in entry.value() {
+            if is_internal(import_value, &internal_packages) {
+                internal_count += 1;
+            } else {
+                *tally.entry(package_prefix(import_value, segments).to_string()).or_default() += 1;
+            }
+        }
+    }
+
+    let mut tally: Vec<(String, usize)> = tally.into_iter().collect();
+    tally.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    (internal_count, tally)
+}
+
+// Function to count the scanned packages, their edges, the root packages and the
+// edges whose import lies outside the scanned packages
+pub fn compute_graph_stats(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+) -> GraphStats {
+    let internal_packages: HashSet<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+
+    let mut edges = 0;
+    let mut external_refs = 0;
+
+    for entry in imports_map.iter() {
+        edges += entry.value().len();
+        external_refs += entry
+            .value()
+            .iter()
+            .filter(|import_value| !is_internal(import_value, &internal_packages))
+            .count();
+    }
+
+    GraphStats {
+        packages: imports_map.len(),
+        edges,
+        roots: root_packages(imports_map, root_class_prefix).len(),
+        external_refs,
+    }
+}
+
+// Function to find the strongly connected components of the import graph
+// (Tarjan's algorithm), with members and components sorted by name
+pub fn find_strongly_connected_components(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> Vec<Vec<String>> {
+    let successors = |package_name: &str| -> Vec<String> {
+        imports_map
+            .get(package_name)
+            .map(|imports| imports.value().clone())
+            .unwrap_or_default()
+    };
+
+    let mut package_names: Vec<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+    package_names.sort();
+
+    let mut index = HashMap::<String, usize>::new();
+    let mut lowlink = HashMap::<String, usize>::new();
+    let mut on_stack = HashSet::<String>::new();
+    let mut component_stack = Vec::<String>::new();
+    let mut components = Vec::<Vec<String>>::new();
+
+    for start in package_names {
+        if index.contains_key(&start) {
+            continue;
+        }
+
+        index.insert(start.clone(), index.len());
+        lowlink.insert(start.clone(), lowlink.len());
+        on_stack.insert(start.clone());
+        component_stack.push(start.clone());
+
+        let start_successors = successors(&start);
+        let mut call_stack = vec![(start, start_successors, 0)];
+
+        while let Some((package_name, imports, next)) = call_stack.last_mut() {
+            if let Some(import_value) = imports.get(*next).cloned() {
+                *next += 1;
+
+                if !index.contains_key(&import_value) {
+                    index.insert(import_value.clone(), index.len());
+                    lowlink.insert(import_value.clone(), lowlink.len());
+                    on_stack.insert(import_value.clone());
+                    component_stack.push(import_value.clone());
+
+                    let import_successors = successors(&import_value);
+                    call_stack.push((import_value, import_successors, 0));
+                } else if on_stack.contains(&import_value) {
+                    let low = lowlink[package_name.as_str()].min(index[&import_value]);
+                    lowlink.insert(package_name.clone(), low);
+                }
+            } else {
+                let package_name = package_name.clone();
+                call_stack.pop();
+
+                if lowlink[&package_name] == index[&package_name] {
+                    let mut component = Vec::new();
+
+                    while let Some(member) = component_stack.pop() {
+                        on_stack.remove(&member);
+                        let is_root = member == package_name;
+                        component.push(member);
+
+                        if is_root {
+                            break;
+                        }
+                    }
+
+                    component.sort();
+                    components.push(component);
+                }
+
+                if let Some((parent, _, _)) = call_stack.last() {
+                    let low = lowlink[parent.as_str()].min(lowlink[&package_name]);
+                    lowlink.insert(parent.clone(), low);
+                }
+            }
+        }
+    }
+
+    components.sort();
+
+    components
+}
+
+// Function to build an imports map holding only the package-level edges
+// between scanned packages, optionally limited to packages with a prefix
+pub fn internal_imports_map(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+) -> DashMap<String, Vec<String>> {
+    let in_scope = |package_name: &String| {
+        root_class_prefix.is_none_or(|root_class_prefix| package_name.starts_with(root_class_prefix))
+    };
+
+    build_internal_graph(imports_map)
+        .into_iter()
+        .filter(|(package_name, _)| in_scope(package_name))
+        .map(|(package_name, dependencies)| {
+            let mut dependencies: Vec<String> = dependencies
+                .into_iter()
+                .filter(|dependency| in_scope(dependency))
+                .collect();
+            dependencies.sort();
+
+            (package_name, dependencies)
+        })
+        .collect()
+}
+
+// Function to find the root packages, which no other scanned package imports, and
+// the leaf packages, which import no other scanned package; imports of a package's
+// own classes and of anything outside the scanned packages count for neither
+pub fn find_roots_and_leaves(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+) -> (Vec<String>, Vec<String>) {
+    let package_map = internal_imports_map(imports_map, root_class_prefix);
+
+    let imported: HashSet<String> = package_map
+        .iter()
+        .flat_map(|entry| entry.value().clone())
+        .collect();
+
+    let mut roots = Vec::new();
+    let mut leaves = Vec::new();
+
+    for entry in package_map.iter() {
+        if !imported.contains(entry.key()) {
+            roots.push(entry.key().to_string());
+        }
+
+        if entry.value().is_empty() {
+            leaves.push(entry.key().to_string());
+        }
+    }
+
+    roots.sort();
+    leaves.sort();
+
+    (roots, leaves)
+}
+
+// An edge from a package to one of its imports
+type Edge = (String, String);
+
+// Function to compute the transitive closure of a package: the package graph limited to
+// the packages it reaches, breadth-first and up to the depth limit (0 keeps only the
+// package itself); packages already visited aren't expanded again, so cycles end
+pub fn find_closure(
+    package_map: &DashMap<String, Vec<String>>,
+    from: &str,
+    depth: Option<usize>,
+) -> DashMap<String, Vec<String>> {
+    let closure = DashMap::<String, Vec<String>>::new();
+    let depth = depth.unwrap_or(usize::MAX);
+
+    let mut visited = HashSet::from([from.to_string()]);
+    let mut queue = VecDeque::from([(from.to_string(), 0)]);
+
+    while let Some((package_name, current_depth)) = queue.pop_front() {
+        closure.entry(package_name.clone()).or_default();
+
+        if current_depth >= depth {
+            continue;
+        }
+
+        let Some(dependencies) = package_map.get(&package_name) else {
+            continue;
+        };
+
+        closure.insert(package_name.clone(), dependencies.clone());
+
+        for dependency in dependencies.iter() {
+            if visited.insert(dependency.clone()) {
+                queue.push_back((dependency.clone(), current_depth + 1));
+            }
+        }
+    }
+
+    closure
+}
+
+// Function to compare the edges of two graphs, returning the edges only the new graph
+// has and those only the old graph has, both sorted
+pub fn diff_edges(
+    old_imports_map: &DashMap<String, Vec<String>>,
+    new_imports_map: &DashMap<String, Vec<String>>,
+) -> (Vec<Edge>, Vec<Edge>) {
+    let old_edges: BTreeSet<Edge> = sorted_edges(old_imports_map.clone()).into_iter().collect();
+    let new_edges: BTreeSet<Edge> = sorted_edges(new_imports_map.clone()).into_iter().collect();
+
+    (
+        new_edges.difference(&old_edges).cloned().collect(),
+        old_edges.difference(&new_edges).cloned().collect(),
+    )
+}
+
+// Function to find the dependency cycles of the new graph whose packages didn't form
+// a cycle in the old graph, e.g. because an existing cycle grew, each as a chain
+pub fn find_new_cycles(
+    old_imports_map: &DashMap<String, Vec<String>>,
+    new_imports_map: &DashMap<String, Vec<String>>,
+) -> Vec<Vec<String>> {
+    let old_components: HashSet<Vec<String>> =
+        find_strongly_connected_components(&internal_imports_map(old_imports_map, None))
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .collect();
+
+    let new_package_map = internal_imports_map(new_imports_map, None);
+
+    find_strongly_connected_components(&new_package_map)
+        .into_iter()
+        .filter(|component| component.len() > 1 && !old_components.contains(component))
+        .map(|component| find_cycle(&new_package_map, &component))
+        .collect()
+}
+
+// Function to find a shortest cycle through the first package of a strongly
+// connected component, returned as a chain starting and ending with that package
+pub fn find_cycle(
+    imports_map: &DashMap<String, Vec<String>>,
+    component: &[String],
+) -> Vec<String> {
+    let members: HashSet<&String> = component.iter().collect();
+    let start = &component[0];
+
+    let mut previous = HashMap::<&String, &String>::new();
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(package_name) = queue.pop_front() {
+        let Some(imports) = imports_map.get(package_name) else {
+            continue;
+        };
+
+        for import_value in imports.iter() {
+            let Some(&import_value) = members.get(import_value) else {
+                continue;
+            };
+
+            if import_value == start {
+                let mut cycle = vec![start.clone(), package_name.clone()];
+
+                while let Some(&before) = previous.get(cycle.last().unwrap()) {
+                    cycle.push(before.clone());
+                }
+
+                cycle.reverse();
+                return cycle;
+            }
+
+            if !previous.contains_key(import_value) {
+                previous.insert(import_value, package_name);
+                queue.push_back(import_value);
+            }
+        }
+    }
+
+    vec![start.clone()]
+}
+
+// Function to find a shortest chain of imports leading from one package to another
+pub fn find_shortest_path(
+    imports_map: &DashMap<String, Vec<String>>,
+    from: &str,
+    to: &str,
+) -> Option<Vec<String>> {
+    let mut previous = HashMap::<String, String>::new();
+    let mut queue = VecDeque::from([from.to_string()]);
+
+    while let Some(package_name) = queue.pop_front() {
+        if package_name == to {
+            let mut path = vec![package_name];
+
+            while let Some(before) = previous.get(path.last().unwrap()) {
+                path.push(before.clone());
+            }
+
+            path.reverse();
+            return Some(path);
+        }
+
+        let Some(imports) = imports_map.get(&package_name) else {
+            continue;
+        };
+
+        for import_value in imports.iter() {
+            if import_value != from && !previous.contains_key(import_value) {
+                previous.insert(import_value.clone(), package_name.clone());
+                queue.push_back(import_value.clone());
+            }
+        }
+    }
+
+    None
+}
+
+// Function to find the longest chain of dependencies in the graph left when every
+// dependency cycle is condensed into one node, as the list of strongly connected
+// components along the chain; ties go to the alphabetically first component
+pub fn find_longest_chain(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> Vec<Vec<String>> {
+    let components = find_strongly_connected_components(imports_map);
+
+    let component_of: HashMap<&str, usize> = components
+        .iter()
+        .enumerate()
+        .flat_map(|(component_id, component)| {
+            component
+                .iter()
+                .map(move |package_name| (package_name.as_str(), component_id))
+        })
+        .collect();
+
+    let mut successors = vec![BTreeSet::<usize>::new(); components.len()];
+    let mut in_degree = vec![0; components.len()];
+
+    for entry in imports_map.iter() {
+        let from = component_of[entry.key().as_str()];
+
+        for import_value in entry.value() {
+            if let Some(&to) = component_of.get(import_value.as_str()) {
+                if to != from && successors[from].insert(to) {
+                    in_degree[to] += 1;
+                }
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(components.len());
+    let mut ready: VecDeque<usize> = (0..components.len())
+        .filter(|&component_id| in_degree[component_id] == 0)
+        .collect();
+
+    while let Some(component_id) = ready.pop_front() {
+        order.push(component_id);
+
+        for &successor in &successors[component_id] {
+            in_degree[successor] -= 1;
+
+            if in_degree[successor] == 0 {
+                ready.push_back(successor);
+            }
+        }
+    }
+
+    // dependencies come later in the order, so their chains are known first
+    let mut chain_length = vec![0; components.len()];
+    let mut next = vec![None; components.len()];
+
+    for &component_id in order.iter().rev() {
+        for &successor in &successors[component_id] {
+            if next[component_id].is_none() || chain_length[successor] + 1 > chain_length[component_id] {
+                chain_length[component_id] = chain_length[successor] + 1;
+                next[component_id] = Some(successor);
+            }
+        }
+    }
+
+    let mut chain = Vec::new();
+    let mut current = (0..components.len()).rev().max_by_key(|&component_id| chain_length[component_id]);
+
+    while let Some(component_id) = current {
+        chain.push(components[component_id].clone());
+        current = next[component_id];
+    }
+
+    chain
+}
+
+// Function to order packages so that every package comes after the packages it
+// depends on (alphabetically among those that are ready), or to return a cycle
+// when no such order exists
+pub fn topological_order(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> Result<Vec<String>, Vec<String>> {
+    let mut pending = HashMap::<String, usize>::new();
+    let mut dependents = HashMap::<String, Vec<String>>::new();
+
+    for entry in imports_map.iter() {
+        pending.entry(entry.key().to_string()).or_default();
+
+        for import_value in entry.value().iter().collect::<HashSet<_>>() {
+            *pending.entry(entry.key().to_string()).or_default() += 1;
+            pending.entry(import_value.to_string()).or_default();
+            dependents
+                .entry(import_value.to_string())
+                .or_default()
+                .push(entry.key().to_string());
+        }
+    }
+
+    let mut ready: BTreeSet<String> = pending
+        .iter()
+        .filter(|(_, &count)| count == 0)
+        .map(|(package_name, _)| package_name.clone())
+        .collect();
+
+    let mut order = Vec::new();
+
+    while let Some(package_name) = ready.pop_first() {
+        for dependent in dependents.get(&package_name).into_iter().flatten() {
+            let count = pending.get_mut(dependent).unwrap();
+            *count -= 1;
+
+            if *count == 0 {
+                ready.insert(dependent.clone());
+            }
+        }
+
+        order.push(package_name);
+    }
+
+    if order.len() < pending.len() {
+        let component = find_strongly_connected_components(imports_map)
+            .into_iter()
+            .find(|component| {
+                component.len() > 1 || imports_map
+                    .get(&component[0])
+                    .is_some_and(|imports| imports.contains(&component[0]))
+            })
+            .unwrap();
+
+        return Err(find_cycle(imports_map, &component));
+    }
+
+    Ok(order)
+}
+
+// Function to find pairs of packages whose import sets have a Jaccard
+// similarity of at least the given threshold
+pub fn find_similar_packages(
+    imports_map: &DashMap<String, Vec<String>>,
+    threshold: f64,
+) -> Vec<(String, String, f64)> {
+    let mut packages: Vec<(String, HashSet<String>)> = imports_map
+        .iter()
+        .filter(|entry| !entry.value().is_empty())
+        .map(|entry| {
+            (
+                entry.key().to_string(),
+                entry.value().iter().cloned().collect(),
+            )
+        })
+        .collect();
+    packages.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut similar = Vec::new();
+
+    for (position, (package_name, imports)) in packages.iter().enumerate() {
+        for (other_name, other_imports) in &packages[position + 1..] {
+            let shared = imports.intersection(other_imports).count();
+            let total = imports.len() + other_imports.len() - shared;
+            let similarity = shared as f64 / total as f64;
+
+            if similarity >= threshold {
+                similar.push((package_name.clone(), other_name.clone(), similarity));
+            }
+        }
+    }
+
+    similar.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    similar
+}
+
+// Function to compute the fan-in (scanned packages importing it) and fan-out
+// (distinct imports from outside the package itself) of every scanned package
+pub fn compute_package_metrics(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> Vec<PackageMetrics> {
+    let internal_packages: HashSet<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+
+    let dependents = invert_internal_graph(&build_internal_graph(imports_map));
+
+    imports_map
+        .iter()
+        .map(|entry| {
+            let package_name = entry.key();
+
+            let fan_out = entry
+                .value()
+                .iter()
+                .filter(|import_value| {
+                    owning_package(import_value, &internal_packages) != Some(package_name.as_str())
+                })
+                .collect::<HashSet<_>>()
+                .len();
+
+            PackageMetrics {
+                package_name: package_name.to_string(),
+                fan_in: dependents.get(package_name).map_or(0, |dependents| dependents.len()),
+                fan_out,
+            }
+        })
+        .collect()
+}
+
+// Function to find the orphan packages, which neither import nor are imported by any
+// other scanned package, paired with their fan-out: 0 for truly isolated packages,
+// the number of external imports for those only connected to external dependencies
+pub fn find_orphans(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+) -> Vec<(String, usize)> {
+    let internal_graph = build_internal_graph(imports_map);
+
+    let mut orphans: Vec<(String, usize)> = compute_package_metrics(imports_map)
+        .into_iter()
+        .filter(|package_metrics| {
+            root_class_prefix.is_none_or(|root_class_prefix| package_metrics.package_name.starts_with(root_class_prefix))
+        })
+        .filter(|package_metrics| {
+            package_metrics.fan_in == 0
+                && internal_graph
+                    .get(&package_metrics.package_name)
+                    .is_none_or(|dependencies| dependencies.is_empty())
+        })
+        .map(|package_metrics| (package_metrics.package_name, package_metrics.fan_out))
+        .collect();
+
+    orphans.sort();
+
+    orphans
+}
+
+// Function to pick the packages whose fan-in or fan-out exceeds its threshold,
+// most over a threshold first, relative to that threshold
+pub fn find_advised_packages(
+    metrics: &[PackageMetrics],
+    max_fan_in: usize,
+    max_fan_out: usize,
+) -> Vec<&PackageMetrics> {
+    let excess = |package_metrics: &PackageMetrics| {
+        (package_metrics.fan_in as f64 / max_fan_in.max(1) as f64)
+            .max(package_metrics.fan_out as f64 / max_fan_out.max(1) as f64)
+    };
+
+    let mut advised: Vec<&PackageMetrics> = metrics
+        .iter()
+        .filter(|package_metrics| package_metrics.fan_in > max_fan_in || package_metrics.fan_out > max_fan_out)
+        .collect();
+
+    advised.sort_by(|a, b| excess(b).total_cmp(&excess(a)).then(a.package_name.cmp(&b.package_name)));
+
+    advised
+}
+
+// Function to find packages that both depend on and are depended upon by many
+// other packages, ranked by the product of their fan-in and fan-out
+pub fn find_hotspots(
+    imports_map: &DashMap<String, Vec<String>>,
+    min_in: usize,
+    min_out: usize,
+) -> Vec<Hotspot> {
+    let internal_graph = build_internal_graph(imports_map);
+    let dependents = invert_internal_graph(&internal_graph);
+
+    let degree = |package_name: &String| {
+        internal_graph.get(package_name).map_or(0, |dependencies| dependencies.len())
+            + dependents.get(package_name).map_or(0, |dependents| dependents.len())
+    };
+
+    // most-connected neighbours first, then by name
+    let ranked = |packages: &HashSet<String>| -> Vec<String> {
+        let mut packages: Vec<String> = packages.iter().cloned().collect();
+        packages.sort_by(|a, b| degree(b).cmp(&degree(a)).then(a.cmp(b)));
+        packages
+    };
+
+    let mut hotspots: Vec<Hotspot> = internal_graph
+        .iter()
+        .filter_map(|(package_name, dependencies)| {
+            let dependents = &dependents[package_name];
+
+            if dependents.len() < min_in || dependencies.len() < min_out {
+                return None;
+            }
+
+            Some(Hotspot {
+                package_name: package_name.clone(),
+                dependencies: ranked(dependencies),
+                dependents: ranked(dependents),
+            })
+        })
+        .collect();
+
+    hotspots.sort_by(|a, b| {
+        b.score()
+            .cmp(&a.score())
+            .then(a.package_name.cmp(&b.package_name))
+    });
+
+    hotspots
+}
+
+// Function to parse a rules file of forbidden dependencies, one "SOURCE -> TARGET"
+// rule of package prefixes per line; blank lines and lines starting with # are skipped
+pub fn parse_dependency_rules(
+    rules_content: &str,
+) -> anyhow::Result<Vec<DependencyRule>> {
+    let mut rules = Vec::new();
+
+    for (line_index, line) in rules_content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let Some((source_prefix, target_prefix)) = line.split_once("->") else {
+            bail!("line {}: expected a rule like \"SOURCE -> TARGET\", found \"{}\"", line_index + 1, line);
+        };
+
+        let (source_prefix, target_prefix) = (source_prefix.trim(), target_prefix.trim());
+
+        if source_prefix.is_empty() || target_prefix.is_empty() {
+            bail!("line {}: both ends of the rule \"{}\" need a package prefix", line_index + 1, line);
+        }
+
+        rules.push(DependencyRule {
+            source_prefix: source_prefix.to_string(),
+            target_prefix: target_prefix.to_string(),
+        });
+    }
+
+    Ok(rules)
+}
+
+// Function to find the edges whose package starts with the source prefix of a rule
+// and whose import starts with its target prefix, along with the broken rule
+pub fn find_rule_violations<'a>(
+    imports_map: &DashMap<String, Vec<String>>,
+    rules: &'a [DependencyRule],
+) -> Vec<(String, String, &'a DependencyRule)> {
+    let mut edges = sorted_edges(imports_map.clone());
+    edges.dedup();
+
+    edges
+        .into_iter()
+        .flat_map(|(package_name, import_value)| {
+            rules
+                .iter()
+                .filter(|rule| {
+                    package_name.starts_with(rule.source_prefix.as_str())
+                        && import_value.starts_with(rule.target_prefix.as_str())
+                })
+                .map(|rule| (package_name.clone(), import_value.clone(), rule))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+pub struct GraphStats {
+    pub packages: usize,
+    pub edges: usize,
+    pub roots: usize,
+    pub external_refs: usize,
+}
+
+// A forbidden dependency from the packages starting with one prefix to the
+// imports starting with another
+pub struct DependencyRule {
+    pub source_prefix: String,
+    pub target_prefix: String,
+}
+
+pub struct Hotspot {
+    pub package_name: String,
+    pub dependencies: Vec<String>,
+    pub dependents: Vec<String>,
+}
+
+impl Hotspot {
+    pub fn score(&self) -> usize {
+        self.dependents.len() * self.dependencies.len()
+    }
+}
+
+pub struct PackageMetrics {
+    pub package_name: String,
+    pub fan_in: usize,
+    pub fan_out: usize,
+}
+
+impl PackageMetrics {
+    // share of a package's couplings that are outgoing; 0 is maximally stable
+    pub fn instability(&self) -> f64 {
+        if self.fan_in + self.fan_out == 0 {
+            return 0.0;
+        }
+
+        self.fan_out as f64 / (self.fan_in + self.fan_out) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::imports_map;
+
+    // Function to list the cycles between the packages of an imports map, as the
+    // cycles subcommand reports them
+    fn cycles(
+        imports_map: &DashMap<String, Vec<String>>,
+    ) -> Vec<Vec<String>> {
+        let package_map = internal_imports_map(imports_map, None);
+
+        find_strongly_connected_components(&package_map)
+            .into_iter()
+            .filter(|component| component.len() > 1)
+            .map(|component| find_cycle(&package_map, &component))
+            .collect()
+    }
+
+    #[test]
+    fn three_package_cycle_is_found() {
+        let imports_map = imports_map(&[
+            ("com.a", &["com.b.B", "java.util.List"]),
+            ("com.b", &["com.c.C"]),
+            ("com.c", &["com.a.A"]),
+        ]);
+
+        assert_eq!(cycles(&imports_map), vec![vec!["com.a", "com.b", "com.c", "com.a"]]);
+    }
+
+    #[test]
+    fn acyclic_graph_has_no_cycles() {
+        let imports_map = imports_map(&[
+            ("com.a", &["com.b.B", "com.c.C"]),
+            ("com.b", &["com.c.C"]),
+            ("com.c", &["java.util.List"]),
+        ]);
+
+        assert!(cycles(&imports_map).is_empty());
+    }
+
+    #[test]
+    fn fan_in_fan_out_and_instability_are_counted() {
+        let imports_map = imports_map(&[
+            ("com.a", &["com.b.B", "com.b.C", "java.util.List"]),
+            ("com.b", &["com.b.C", "com.c.D"]),
+            ("com.c", &[]),
+        ]);
+
+        let mut metrics: Vec<(String, usize, usize, f64)> = compute_package_metrics(&imports_map)
+            .into_iter()
+            .map(|package_metrics| {
+                let instability = package_metrics.instability();
+                (package_metrics.package_name, package_metrics.fan_in, package_metrics.fan_out, instability)
+            })
+            .collect();
+        metrics.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(
+            metrics,
+            vec![
+                ("com.a".to_string(), 0, 3, 1.0),
+                ("com.b".to_string(), 1, 1, 0.5),
+                ("com.c".to_string(), 1, 0, 0.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn build_order_puts_dependencies_first_and_refuses_cycles() {
+        let acyclic = internal_imports_map(
+            &imports_map(&[
+                ("com.app", &["com.core.Core", "com.util.Util"]),
+                ("com.core", &["com.util.Util"]),
+                ("com.util", &["java.util.List"]),
+                ("com.api", &[]),
+            ]),
+            None,
+        );
+
+        assert_eq!(topological_order(&acyclic), Ok(vec![
+            "com.api".to_string(),
+            "com.util".to_string(),
+            "com.core".to_string(),
+            "com.app".to_string(),
+        ]));
+
+        let cyclic = internal_imports_map(
+            &imports_map(&[
+                ("com.app", &["com.core.Core"]),
+                ("com.core", &["com.util.Util"]),
+                ("com.util", &["com.core.Core"]),
+            ]),
+            None,
+        );
+
+        assert_eq!(topological_order(&cyclic), Err(vec![
+            "com.core".to_string(),
+            "com.util".to_string(),
+            "com.core".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn shortest_path_is_found_between_reachable_packages_only() {
+        let package_map = internal_imports_map(
+            &imports_map(&[
+                ("com.a", &["com.b.B", "com.c.C"]),
+                ("com.b", &["com.d.D"]),
+                ("com.c", &["com.e.E"]),
+                ("com.e", &["com.d.D"]),
+                ("com.d", &[]),
+            ]),
+            None,
+        );
+
+        assert_eq!(
+            find_shortest_path(&package_map, "com.a", "com.d"),
+            Some(vec!["com.a".to_string(), "com.b".to_string(), "com.d".to_string()]),
+        );
+        assert_eq!(find_shortest_path(&package_map, "com.d", "com.a"), None);
+    }
+
+    #[test]
+    fn external_imports_are_tallied_by_namespace_root() {
+        let imports_map = imports_map(&[
+            ("com.acme.app", &["com.acme.core.Core", "java.util.List", "java.io.File", "org.springframework.Bean"]),
+            ("com.acme.core", &["java.util.Map", "org.slf4j.Logger"]),
+        ]);
+
+        assert_eq!(
+            tally_external_imports(&imports_map, 1),
+            (1, vec![("java".to_string(), 3), ("org".to_string(), 2)]),
+        );
+        assert_eq!(
+            tally_external_imports(&imports_map, 2),
+            (1, vec![
+                ("java.util".to_string(), 2),
+                ("java.io".to_string(), 1),
+                ("org.slf4j".to_string(), 1),
+                ("org.springframework".to_string(), 1),
+            ]),
+        );
+    }
+
+    #[test]
+    fn longest_chain_follows_the_deepest_dependencies() {
+        let linear = internal_imports_map(
+            &imports_map(&[
+                ("com.a", &["com.b.B", "com.d.D"]),
+                ("com.b", &["com.c.C"]),
+                ("com.c", &["com.d.D"]),
+                ("com.d", &["java.util.List"]),
+            ]),
+            None,
+        );
+
+        assert_eq!(
+            find_longest_chain(&linear),
+            vec![vec!["com.a"], vec!["com.b"], vec!["com.c"], vec!["com.d"]],
+        );
+
+        let cyclic = internal_imports_map(
+            &imports_map(&[
+                ("com.a", &["com.b.B"]),
+                ("com.b", &["com.c.C"]),
+                ("com.c", &["com.b.B", "com.d.D"]),
+                ("com.d", &[]),
+            ]),
+            None,
+        );
+
+        assert_eq!(
+            find_longest_chain(&cyclic),
+            vec![vec!["com.a"], vec!["com.b", "com.c"], vec!["com.d"]],
+        );
+    }
+
+    #[test]
+    fn roots_and_leaves_ignore_own_and_external_imports() {
+        let imports_map = imports_map(&[
+            ("com.app", &["com.core.Service", "java.util.List"]),
+            ("com.cli", &["com.core.Service"]),
+            ("com.core", &["com.core.Helper", "org.slf4j.Logger"]),
+            ("com.tool", &[]),
+        ]);
+
+        let (roots, leaves) = find_roots_and_leaves(&imports_map, None);
+
+        assert_eq!(roots, vec!["com.app", "com.cli", "com.tool"]);
+        assert_eq!(leaves, vec!["com.core", "com.tool"]);
+    }
+
+    #[test]
+    fn forbidden_dependencies_are_reported_until_they_are_gone() {
+        let rules = parse_dependency_rules(
+            "# the web layer goes through services\n\
+             com.acme.web -> com.acme.persistence\n\
+             \n\
+             com.acme.persistence -> com.acme.web\n",
+        )
+        .unwrap();
+
+        let violating = imports_map(&[
+            ("com.acme.web", &["com.acme.service.Orders", "com.acme.persistence.OrderRepository"]),
+            ("com.acme.service", &["com.acme.persistence.OrderRepository"]),
+        ]);
+        let passing = imports_map(&[
+            ("com.acme.web", &["com.acme.service.Orders"]),
+            ("com.acme.service", &["com.acme.persistence.OrderRepository"]),
+        ]);
+
+        let violations: Vec<(String, String, String)> = find_rule_violations(&violating, &rules)
+            .into_iter()
+            .map(|(package_name, import_value, rule)| (package_name, import_value, rule.target_prefix.clone()))
+            .collect();
+
+        assert_eq!(
+            violations,
+            vec![(
+                "com.acme.web".to_string(),
+                "com.acme.persistence.OrderRepository".to_string(),
+                "com.acme.persistence".to_string(),
+            )],
+        );
+        assert!(find_rule_violations(&passing, &rules).is_empty());
+        assert!(parse_dependency_rules("com.acme.web persistence\n").is_err());
+    }
+
+    #[test]
+    fn graph_stats_serialize_to_one_json_line() {
+        let imports_map = imports_map(&[
+            ("com.a", &["com.b.B", "java.util.List"]),
+            ("com.b", &["com.c.C", "org.slf4j.Logger"]),
+            ("com.c", &[]),
+        ]);
+
+        let stats_json = serde_json::to_string(&compute_graph_stats(&imports_map, Some("com.a"))).unwrap();
+
+        assert_eq!(stats_json, r#"{"packages":3,"edges":4,"roots":1,"external_refs":2}"#);
+    }
+
+    #[test]
+    fn orphans_are_isolated_or_only_connected_to_external_packages() {
+        let imports_map = imports_map(&[
+            ("com.app", &["com.core.Service"]),
+            ("com.core", &[]),
+            ("com.legacy", &[]),
+            ("com.tool", &["java.util.List", "org.slf4j.Logger"]),
+        ]);
+
+        assert_eq!(
+            find_orphans(&imports_map, None),
+            vec![("com.legacy".to_string(), 0), ("com.tool".to_string(), 2)],
+        );
+    }
+
+    #[test]
+    fn packages_over_a_fan_threshold_are_advised_most_over_first() {
+        let package_metrics = |package_name: &str, fan_in, fan_out| PackageMetrics {
+            package_name: package_name.to_string(),
+            fan_in,
+            fan_out,
+        };
+        let metrics = vec![
+            package_metrics("com.calm", 2, 3),
+            package_metrics("com.depended", 9, 1),
+            package_metrics("com.edge", 4, 6),
+            package_metrics("com.god", 3, 15),
+        ];
+
+        let advised: Vec<&str> = find_advised_packages(&metrics, 4, 5)
+            .into_iter()
+            .map(|package_metrics| package_metrics.package_name.as_str())
+            .collect();
+
+        assert_eq!(advised, vec!["com.god", "com.depended", "com.edge"]);
+    }
+
+    #[test]
+    fn diff_reports_added_and_removed_edges_and_new_cycles() {
+        let old_imports_map = imports_map(&[
+            ("com.a", &["com.b.B", "java.util.List"]),
+            ("com.b", &["com.c.C"]),
+            ("com.c", &[]),
+        ]);
+        let new_imports_map = imports_map(&[
+            ("com.a", &["com.b.B"]),
+            ("com.b", &["com.c.C"]),
+            ("com.c", &["com.a.A"]),
+        ]);
+
+        let edge = |package_name: &str, import_value: &str| (package_name.to_string(), import_value.to_string());
+
+        assert_eq!(
+            diff_edges(&old_imports_map, &new_imports_map),
+            (vec![edge("com.c", "com.a.A")], vec![edge("com.a", "java.util.List")]),
+        );
+        assert_eq!(
+            find_new_cycles(&old_imports_map, &new_imports_map),
+            vec![vec!["com.a", "com.b", "com.c", "com.a"]],
+        );
+        assert!(find_new_cycles(&new_imports_map, &new_imports_map).is_empty());
+    }
+
+    #[test]
+    fn closure_reaches_every_dependency_once_despite_cycles() {
+        let package_map = internal_imports_map(
+            &imports_map(&[
+                ("com.a", &["com.b.B"]),
+                ("com.b", &["com.c.C"]),
+                ("com.c", &["com.b.B", "com.d.D"]),
+                ("com.d", &[]),
+                ("com.unrelated", &["com.a.A"]),
+            ]),
+            None,
+        );
+
+        let reachable = |depth| {
+            let mut package_names: Vec<String> = find_closure(&package_map, "com.a", depth)
+                .iter()
+                .map(|entry| entry.key().to_string())
+                .collect();
+            package_names.sort();
+            package_names
+        };
+
+        assert_eq!(reachable(None), vec!["com.a", "com.b", "com.c", "com.d"]);
+        assert_eq!(reachable(Some(2)), vec!["com.a", "com.b", "com.c"]);
+        assert_eq!(reachable(Some(0)), vec!["com.a"]);
+    }
+
+    #[test]
+    fn external_libraries_are_counted_once_per_two_segment_prefix() {
+        let imports_map = imports_map(&[
+            (
+                "com.a",
+                &["com.b.B", "org.slf4j.Logger", "org.slf4j.LoggerFactory", "com.google.common.collect.Lists", "java.util.List"],
+            ),
+            ("com.b", &[]),
+        ]);
+
+        let external_libraries = collect_external_libraries(&imports_map);
+
+        assert_eq!(
+            external_libraries["com.a"].iter().collect::<Vec<_>>(),
+            vec!["com.google", "java.util", "org.slf4j"],
+        );
+        assert!(external_libraries["com.b"].is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use dashmap::DashMap;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::parse::{parse_package_imports, report_cached_parse_problems, ParseOptions, SourceFile};
+
+// Version of the layout of the cached files, raised whenever CachedFile changes so
+// that caches written by other versions are discarded rather than misread
+const CACHE_FORMAT: u32 = 4;
+
+// Package and imports parsed from a file, as stored in the parse cache along with
+// the number of parse problems it had; files that couldn't be parsed are stored
+// without them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedFile {
+    pub modified_secs: u64,
+    pub modified_nanos: u32,
+    pub source: Option<SourceFile>,
+    pub problems: usize,
+}
+
+// The parse cache as written to disk, along with its format and the fingerprint of
+// the settings its files were parsed with
+#[derive(Serialize, Deserialize, Default)]
+struct CacheContent {
+    #[serde(default)]
+    format: u32,
+    fingerprint: String,
+    files: BTreeMap<String, CachedFile>,
+}
+
+// On-disk cache of parsed files keyed by path, reused while a file's
+// modification time is unchanged and the files are parsed with the same settings
+#[derive(Default)]
+pub struct ParseCache {
+    fingerprint: String,
+    previous: HashMap<String, CachedFile>,
+    current: DashMap<String, CachedFile>,
+    pub reused: AtomicUsize,
+    pub parsed: AtomicUsize,
+}
+
+impl ParseCache {
+    // a cache written with other settings, see WalkFilter::fingerprint, is discarded
+    pub fn load(cache_path: &Path, fingerprint: &str) -> ParseCache {
+        let cache_content = match fs::read_to_string(cache_path) {
+            Ok(cache_content) => serde_json::from_str(&cache_content).unwrap_or_else(|error| {
+                warn!("ignoring unreadable cache {}: {}", cache_path.display(), error);
+                CacheContent::default()
+            }),
+            Err(_) => CacheContent::default(),
+        };
+
+        let previous =
+            if cache_content.format == CACHE_FORMAT && cache_content.fingerprint == fingerprint {
+                cache_content.files.into_iter().collect()
+            } else {
+                if !cache_content.files.is_empty() {
+                    info!("Discarding cache {}, it was written with other parse options or by another version", cache_path.display());
+                }
+
+                HashMap::new()
+            };
+
+        ParseCache {
+            fingerprint: fingerprint.to_string(),
+            previous,
+            ..ParseCache::default()
+        }
+    }
+
+    pub fn parse(&self, file_path: &Path, parse_options: &ParseOptions) -> Option<SourceFile> {
+        let Some(modified) = fs::metadata(file_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok()) else {
+            return parse_package_imports(file_path, parse_options);
+        };
+
+        let key = file_path.display().to_string();
+
+        let cached_file = match self.previous.get(&key) {
+            Some(cached_file)
+                if cached_file.modified_secs == modified.as_secs()
+                    && cached_file.modified_nanos == modified.subsec_nanos() => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+
+                if cached_file.problems > 0 {
+                    report_cached_parse_problems(parse_options, &key, cached_file.problems);
+                }
+
+                cached_file.clone()
+            }
+            _ => {
+                self.parsed.fetch_add(1, Ordering::Relaxed);
+
+                // the file's own problems are counted apart to be stored with it
+                let file_options = ParseOptions {
+                    problems: Arc::new(AtomicUsize::new(0)),
+                    ..parse_options.clone()
+                };
+                let source = parse_package_imports(file_path, &file_options);
+                let problems = file_options.problems.load(Ordering::Relaxed);
+                parse_options.problems.fetch_add(problems, Ordering::Relaxed);
+
+                CachedFile {
+                    modified_secs: modified.as_secs(),
+                    modified_nanos: modified.subsec_nanos(),
+                    source,
+                    problems,
+                }
+            }
+        };
+
+        self.current.insert(key, cached_file.clone());
+
+        cached_file.source
+    }
+
+    // entries of files not seen in this run are kept as long as the file exists
+    pub fn save(&self, cache_path: &Path) -> std::io::Result<()> {
+        let mut files: BTreeMap<String, CachedFile> = self
+            .previous
+            .iter()
+            .filter(|(file_path, _)| Path::new(file_path).is_file())
+            .map(|(file_path, cached_file)| (file_path.clone(), cached_file.clone()))
+            .collect();
+
+        for entry in self.current.iter() {
+            files.insert(entry.key().to_string(), entry.value().clone());
+        }
+
+        let cache_content = CacheContent {
+            format: CACHE_FORMAT,
+            fingerprint: self.fingerprint.clone(),
+            files,
+        };
+
+        fs::write(cache_path, serde_json::to_string(&cache_content)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn cache_written_with_other_parse_options_is_discarded() {
+        let folder = fixture("cache-options", &[("App.java", "package com.acme.app;\nimport com.acme.core.Service;\n")]);
+
+        let source_path = folder.join("App.java");
+        let cache_path = folder.join("cache.json");
+
+        let parse_cache = ParseCache::load(&cache_path, "import-regex=None");
+        parse_cache.parse(&source_path, &ParseOptions::default());
+        parse_cache.save(&cache_path).unwrap();
+
+        let same_options = ParseCache::load(&cache_path, "import-regex=None");
+        same_options.parse(&source_path, &ParseOptions::default());
+
+        let other_options = ParseCache::load(&cache_path, "import-regex=Some(\"x(y)\")");
+        other_options.parse(&source_path, &ParseOptions::default());
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(same_options.reused.load(Ordering::Relaxed), 1);
+        assert_eq!(other_options.reused.load(Ordering::Relaxed), 0);
+        assert_eq!(other_options.parsed.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn only_touched_files_are_parsed_again() {
+        let folder = fixture("cache-touch", &[
+            ("App.java", "package com.acme.app;\nimport com.acme.core.Service;\n"),
+            ("Core.java", "package com.acme.core;\n"),
+        ]);
+
+        let app_path = folder.join("App.java");
+        let core_path = folder.join("Core.java");
+        let cache_path = folder.join("cache.json");
+
+        let first_run = ParseCache::load(&cache_path, "");
+        first_run.parse(&app_path, &ParseOptions::default());
+        first_run.parse(&core_path, &ParseOptions::default());
+        first_run.save(&cache_path).unwrap();
+
+        fs::write(&app_path, "package com.acme.app;\nimport com.acme.core.Repository;\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&app_path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + std::time::Duration::from_secs(1))
+            .unwrap();
+
+        let second_run = ParseCache::load(&cache_path, "");
+        let app_source = second_run.parse(&app_path, &ParseOptions::default()).unwrap();
+        second_run.parse(&core_path, &ParseOptions::default());
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(first_run.parsed.load(Ordering::Relaxed), 2);
+        assert_eq!(second_run.parsed.load(Ordering::Relaxed), 1);
+        assert_eq!(second_run.reused.load(Ordering::Relaxed), 1);
+        assert_eq!(app_source.import_targets(), vec!["com.acme.core.Repository".to_string()]);
+    }
+
+    #[test]
+    fn cached_parse_problems_are_reported_again() {
+        let folder = fixture("cache-problems", &[("App.java", "package com.acme.app;\npackage com.acme.other;\n")]);
+
+        let source_path = folder.join("App.java");
+        let cache_path = folder.join("cache.json");
+
+        let first_options = ParseOptions::default();
+        let first_run = ParseCache::load(&cache_path, "");
+        first_run.parse(&source_path, &first_options);
+        first_run.save(&cache_path).unwrap();
+
+        let second_options = ParseOptions::default();
+        let second_run = ParseCache::load(&cache_path, "");
+        second_run.parse(&source_path, &second_options);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(second_run.reused.load(Ordering::Relaxed), 1);
+        assert_eq!(first_options.problems.load(Ordering::Relaxed), 1);
+        assert_eq!(second_options.problems.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,3156 @@
+// Text exports written with a chosen byte order mark and line ending
+mod output;
+
+// Regenerating a graph on every change to the sources
+mod watch;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
+use dashmap::DashMap;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use jadep::analysis::{
+    collect_external_libraries,
+    compute_graph_stats,
+    compute_package_metrics,
+    diff_edges,
+    find_advised_packages,
+    find_closure,
+    find_cycle,
+    find_hotspots,
+    find_longest_chain,
+    find_new_cycles,
+    find_orphans,
+    find_roots_and_leaves,
+    find_rule_violations,
+    find_shortest_path,
+    find_similar_packages,
+    find_strongly_connected_components,
+    internal_imports_map,
+    parse_dependency_rules,
+    tally_external_imports,
+    topological_order,
+};
+use jadep::cache::ParseCache;
+use jadep::graphviz::{render_graph, run_graphviz, stream_to_file, ImageFormat};
+use jadep::parse::{Language, ParseOptions, SourceEncoding, DEFAULT_LANGUAGES};
+use jadep::render::{
+    escape_dot_string,
+    export_package_imports,
+    files_tooltip,
+    generate_dot_content,
+    generate_graph_content,
+    generate_html_content,
+    weight_edge_attributes,
+    write_dot_content,
+    GraphFormat,
+    GraphStyle,
+    PackageImports,
+    RankDir,
+};
+use jadep::scan::{
+    collect_edge_provenance,
+    collect_source_files,
+    count_package_churn,
+    discover_source_roots,
+    find_package_mismatches,
+    find_wildcard_imports,
+    is_archive,
+    merge_imports_maps,
+    parse_archive,
+    parse_listed_files,
+    query_bazel_sources,
+    read_file_list,
+    resolve_bazel_labels,
+    resolve_wildcard_imports,
+    traverse_folder_par,
+    BuildSystem,
+    Layout,
+    ParsedFile,
+    SourceSet,
+    WalkFilter,
+};
+use jadep::transform::{
+    collapse_imports_map,
+    collapse_imports_to_packages,
+    count_edge_weights,
+    drop_same_top_level_edges,
+    import_package,
+    invert_imports_map,
+    is_internal,
+    keep_edges_to,
+    merge_nested_imports,
+    normalize_external_case,
+    outer_type,
+    package_prefix,
+    partition_by_prefix,
+    rekey_imports_map,
+    rekey_name,
+    rename_edge_weights,
+    rename_package_files,
+    Granularity,
+};
+use jadep::tree::{
+    build_dependency_tree,
+    build_dominator_tree,
+    count_dominated,
+    depth_budget_report,
+    root_packages,
+    TreeOptions,
+};
+
+use output::{write_text_output, TextEncoding};
+use watch::{watch_cache_path, watch_folder, watch_graph_command};
+
+// Share of reachable packages a package must dominate to be highlighted as a chokepoint
+const CHOKEPOINT_PERCENT: usize = 10;
+
+// Number of top dependencies and dependents listed for each hotspot
+const HOTSPOT_NEIGHBOURS: usize = 5;
+
+// Config file read for graph defaults when --config isn't given
+const DEFAULT_CONFIG: &str = "jadep.toml";
+
+// Function to summarize the scope of a graph for --dry-run: the scanned packages
+// and edges, the nodes and edges left after the filters, and the root packages
+fn dry_run_summary(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+) -> String {
+    let edge_count: usize = imports_map.iter().map(|entry| entry.value().len()).sum();
+
+    let dependency_tree = build_dependency_tree(imports_map, root_class_prefix, tree_options);
+
+    let mut graph_nodes = HashSet::new();
+    let mut graph_edge_count = 0;
+
+    for entry in dependency_tree.iter() {
+        graph_nodes.insert(entry.key().to_string());
+        graph_nodes.extend(entry.value().iter().cloned());
+        graph_edge_count += entry.value().len();
+    }
+
+    let mut roots = root_packages(imports_map, root_class_prefix);
+    roots.sort();
+
+    let mut summary = format!(
+        "Packages: {}\nEdges: {}\nGraph nodes: {}\nGraph edges: {}\nRoots ({}):\n",
+        imports_map.len(),
+        edge_count,
+        graph_nodes.len(),
+        graph_edge_count,
+        roots.len(),
+    );
+
+    for root in roots {
+        summary += &format!("  {}\n", root);
+    }
+
+    summary
+}
+
+// Function to build the glob set of the paths skipped with --exclude
+fn exclude_globs(
+    exclude: &[String],
+) -> anyhow::Result<GlobSet> {
+    let mut exclude_builder = GlobSetBuilder::new();
+
+    for pattern in exclude {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                exclude_builder.add(glob);
+            }
+            Err(error) => {
+                bail!("invalid exclude glob: {}", error);
+            }
+        }
+    }
+
+    exclude_builder.build().context("invalid exclude globs")
+}
+
+// Function to parse the languages given with --lang, defaulting to java and kotlin
+fn parse_languages(
+    lang: &[String],
+) -> anyhow::Result<Vec<Language>> {
+    if lang.is_empty() {
+        return Ok(DEFAULT_LANGUAGES.to_vec());
+    }
+
+    let mut languages = Vec::new();
+
+    for language in lang {
+        let Ok(language) = Language::from_str(language.as_str()) else {
+            bail!("unsupported language {}; expected java, kotlin, scala or groovy", language);
+        };
+
+        languages.push(language);
+    }
+
+    Ok(languages)
+}
+
+// Function to build the walk filter of the subcommands that list source files
+// rather than scan them into a graph
+fn source_walk_filter(
+    exclude: &[String],
+    gitignore: bool,
+    follow_symlinks: bool,
+    lang: &[String],
+    parse_options: &ParseOptions,
+) -> anyhow::Result<WalkFilter> {
+    Ok(WalkFilter {
+        exclude: Arc::new(exclude_globs(exclude)?),
+        respect_gitignore: gitignore,
+        languages: parse_languages(lang)?,
+        parse_options: parse_options.clone(),
+        follow_symlinks,
+        ..WalkFilter::default()
+    })
+}
+
+// Function to parse the folder or archive given as the path of a subcommand
+fn scan_input(
+    path: &str,
+    parse_options: &ParseOptions,
+) -> anyhow::Result<DashMap<String, Vec<String>>> {
+    let input_path = input_path(path)?;
+
+    let walk_filter = WalkFilter {
+        parse_options: parse_options.clone(),
+        ..WalkFilter::default()
+    };
+
+    if is_archive(input_path) {
+        parse_archive(input_path, None, &walk_filter, None)
+    } else {
+        Ok(traverse_folder_par(input_path.to_path_buf(), None, &walk_filter, None, None))
+    }
+}
+
+// Function to load the imports map of a folder or archive, or of a graph previously
+// exported by the json subcommand
+fn load_graph_input(
+    path: &str,
+    parse_options: &ParseOptions,
+) -> anyhow::Result<DashMap<String, Vec<String>>> {
+    if !(path.ends_with(".json") && Path::new(path).is_file()) {
+        return scan_input(path, parse_options);
+    }
+
+    let json_content = fs::read_to_string(path)
+        .with_context(|| format!("could not read {}", path))?;
+
+    // the export may start with a byte order mark, see --output-encoding
+    let packages: Vec<PackageImports> = serde_json::from_str(json_content.trim_start_matches('\u{feff}'))
+        .with_context(|| format!("{} is not a graph exported by the json subcommand", path))?;
+
+    // the export also lists every imported class as an external node, which the
+    // scanned packages' imports already stand for
+    Ok(
+        packages
+            .into_iter()
+            .filter(|package_imports| !package_imports.external)
+            .map(|package_imports| (package_imports.package, package_imports.imports))
+            .collect()
+    )
+}
+
+// Function to stop with an error, before any output is generated or graphviz is
+// started, when the dependency tree would hold more than max_nodes nodes
+fn check_max_nodes(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+    max_nodes: usize,
+) -> anyhow::Result<()> {
+    let dependency_tree = build_dependency_tree(imports_map, root_class_prefix, tree_options);
+
+    let mut package_names = HashSet::new();
+
+    for entry in dependency_tree.iter() {
+        package_names.insert(entry.key().to_string());
+        package_names.extend(entry.value().iter().cloned());
+    }
+
+    if package_names.len() > max_nodes {
+        bail!(
+            "the graph has {} nodes, more than the maximum of {}; narrow it down with --class-prefix or --depth",
+            package_names.len(),
+            max_nodes,
+        );
+    }
+
+    Ok(())
+}
+
+// Function to check that an input path given on the command line exists
+fn input_path(
+    path: &str,
+) -> anyhow::Result<&Path> {
+    let input_path = Path::new(path);
+
+    if !input_path.exists() {
+        bail!("input path {} does not exist", path);
+    }
+
+    Ok(input_path)
+}
+
+// Function to compute the output path used when none is given explicitly
+fn default_output_path(
+    root_class_prefix: Option<&str>,
+    extension: &str,
+) -> PathBuf {
+    let file_stem = root_class_prefix.unwrap_or("graph");
+
+    PathBuf::from(format!("{}.{}", file_stem, extension))
+}
+
+// Function to warn when an explicit output path doesn't match the output format
+fn warn_extension_mismatch(
+    output_path: &Path,
+    extension: &str,
+) {
+    let matches = output_path
+        .extension()
+        .and_then(|actual| actual.to_str())
+        .is_some_and(|actual| actual.eq_ignore_ascii_case(extension));
+
+    if !matches {
+        warn!(
+            "output file \"{}\" does not have the .{} extension of the selected format",
+            output_path.display(),
+            extension,
+        );
+    }
+}
+
+// Writer hashing everything written to it with FNV-1a, so generated content can be
+// compared with an earlier run without keeping it in memory
+struct HashingWriter {
+    hash: u64,
+}
+
+impl HashingWriter {
+    fn new() -> HashingWriter {
+        HashingWriter { hash: 0xcbf2_9ce4_8422_2325 }
+    }
+}
+
+impl Write for HashingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &byte in buf {
+            self.hash = (self.hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Function to get the path of the file holding the hash of the dot source an image
+// was rendered from, next to the image itself
+fn render_hash_path(
+    output_path: &Path,
+) -> PathBuf {
+    let mut hash_path = output_path.as_os_str().to_os_string();
+    hash_path.push(".hash");
+
+    PathBuf::from(hash_path)
+}
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Commands,
+
+    /// Only print warnings and errors besides the requested output
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub(crate) quiet: bool,
+
+    /// Also print per-folder scanning progress and timings
+    #[arg(short, long, global = true)]
+    pub(crate) verbose: bool,
+
+    /// Optional number of threads to parse files with; defaults to one per core, 1 parses sequentially
+    #[arg(short, long, global = true, value_name = "N")]
+    pub(crate) jobs: Option<usize>,
+
+    /// Optional regex finding package declarations instead of the built-in one; its
+    /// first capture group is the package name, and comments and strings aren't skipped
+    #[arg(long, global = true, value_name = "REGEX")]
+    pub(crate) package_regex: Option<String>,
+
+    /// Optional regex finding imports instead of the built-in one; its first capture
+    /// group is the imported name, comments and strings aren't skipped, and such imports
+    /// are never treated as static
+    #[arg(long, global = true, value_name = "REGEX")]
+    pub(crate) import_regex: Option<String>,
+}
+
+// Function to parse a rank direction in any case, describing the accepted ones on error
+fn parse_rank_dir(
+    rank_dir: &str,
+) -> Result<RankDir, String> {
+    RankDir::from_str(rank_dir)
+        .map_err(|_| format!("unsupported rank direction \"{}\"; expected tb, bt, lr or rl", rank_dir))
+}
+
+// Defaults for the graph options read from a TOML config file; every key is
+// optional and named after its command line option
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct GraphConfig {
+    layout: Option<String>,
+    build_system: Option<String>,
+    targets: Option<String>,
+    exclude: Vec<String>,
+    gitignore: bool,
+    follow_symlinks: bool,
+    modules: bool,
+    lang: Vec<String>,
+    encoding: Option<String>,
+    exclude_tests: bool,
+    separate_tests: bool,
+    cache: Option<String>,
+    class_prefix: Option<String>,
+    depth: Option<usize>,
+    node_budget: Option<usize>,
+    max_nodes: Option<usize>,
+    internal_only: bool,
+    target_prefix: Option<String>,
+    exclude_package: Vec<String>,
+    bridge: bool,
+    rank_dir: Option<String>,
+    image_format: Option<String>,
+    dot_bin: Option<String>,
+    timeout: Option<u64>,
+    format: Option<String>,
+    normalize_external_case: bool,
+    annotate_scc: bool,
+    keep_edges_to: Vec<String>,
+    node_key: Option<String>,
+    resolve_wildcards: Option<String>,
+    top_level_only: Option<usize>,
+    tooltips: bool,
+    annotate_external_counts: bool,
+    output_dir: Option<String>,
+    reverse: bool,
+    no_color: bool,
+    cluster_depth: Option<usize>,
+    bg_color: Option<String>,
+    edge_color: Option<String>,
+    node_color: Option<String>,
+    graph_attr: Vec<String>,
+    node_attr: Vec<String>,
+    edge_attr: Vec<String>,
+    collapse_depth: Option<usize>,
+    granularity: Option<String>,
+    merge_nested: bool,
+    weighted: bool,
+    progress: Option<String>,
+    strict: bool,
+    output_encoding: Option<String>,
+    line_ending: Option<String>,
+}
+
+impl GraphConfig {
+    // Function to load the given config file, or ./jadep.toml if it exists
+    fn load(config_path: Option<&str>) -> anyhow::Result<GraphConfig> {
+        let config_path =
+            match config_path {
+                Some(config_path) => Path::new(config_path),
+                None if Path::new(DEFAULT_CONFIG).is_file() => Path::new(DEFAULT_CONFIG),
+                None => return Ok(GraphConfig::default()),
+            };
+
+        debug!("Reading options from {}", config_path.display());
+
+        let config_content = fs::read_to_string(config_path)
+            .with_context(|| format!("could not read config {}", config_path.display()))?;
+
+        toml::from_str(&config_content)
+            .with_context(|| format!("invalid config {}", config_path.display()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+enum ProgressMode {
+    #[serde(rename = "auto")]
+    Auto,
+    #[serde(rename = "always")]
+    Always,
+    #[serde(rename = "never")]
+    Never,
+}
+
+impl ProgressMode {
+    // Auto only shows progress on an interactive terminal, and never with --quiet
+    fn is_shown(&self) -> bool {
+        if !log::log_enabled!(log::Level::Info) {
+            return false;
+        }
+
+        match self {
+            ProgressMode::Auto => std::io::stdout().is_terminal(),
+            ProgressMode::Always => true,
+            ProgressMode::Never => false,
+        }
+    }
+}
+
+impl FromStr for ProgressMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ProgressMode::Auto),
+            "always" => Ok(ProgressMode::Always),
+            "never" => Ok(ProgressMode::Never),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+enum MetricsSortKey {
+    #[serde(rename = "fan-in")]
+    FanIn,
+    #[serde(rename = "fan-out")]
+    FanOut,
+    #[serde(rename = "instability")]
+    Instability,
+}
+
+impl FromStr for MetricsSortKey {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "fan-in" => Ok(MetricsSortKey::FanIn),
+            "fan-out" => Ok(MetricsSortKey::FanOut),
+            "instability" => Ok(MetricsSortKey::Instability),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
+pub(crate) enum Commands {
+    /// Generate a graphviz graph from a folder of java or kotlin files
+    Graph {
+        /// Path to a folder, or a jar or zip archive such as a sources jar, containing java or
+        /// kotlin files; may be repeated to combine several source roots into one graph, defaults
+        /// to the current directory when --files-from or --build-system is given
+        #[arg(short, long, value_name = "PATH", required_unless_present_any = ["files_from", "build_system"])]
+        path: Vec<String>,
+
+        /// Optional file listing the source files to parse, one per line ("-" for stdin),
+        /// instead of scanning the path
+        #[arg(long, value_name = "FILE")]
+        files_from: Option<String>,
+
+        /// Optional JSON file caching every parsed file, so unchanged files (by modification
+        /// time) are not parsed again on the next run; it's discarded when the files are
+        /// parsed with other options, such as --encoding, --lang or --import-regex
+        #[arg(long, value_name = "PATH")]
+        cache: Option<String>,
+
+        /// Optional project layout (maven or gradle) whose source roots are discovered below the path
+        #[arg(long, value_name = "LAYOUT")]
+        layout: Option<String>,
+
+        /// Optional build system to take the source files from instead of walking the path:
+        /// bazel runs bazel query in the workspace at the path for the sources of the java and
+        /// kotlin libraries matching --targets; with --files-from, the list may instead hold
+        /// the output of such a query, source file labels like //com/acme/app:App.java
+        #[arg(long, value_name = "SYSTEM", conflicts_with = "layout")]
+        build_system: Option<String>,
+
+        /// Optional bazel target pattern whose libraries are scanned; defaults to //...
+        #[arg(long, value_name = "PATTERN", requires = "build_system")]
+        targets: Option<String>,
+
+        /// Glob of paths to skip while scanning, e.g. "**/build/**"; may be repeated
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Skip paths ignored by .gitignore files in the scanned folders
+        #[arg(long)]
+        gitignore: bool,
+
+        /// Descend into symbolic links to folders; each folder is still scanned only once,
+        /// so links back to an ancestor don't loop
+        #[arg(long)]
+        follow_symlinks: bool,
+
+        /// Parse module-info.java files, turning their requires directives into edges
+        /// between modules
+        #[arg(long)]
+        modules: bool,
+
+        /// Comma-separated languages whose source files are parsed: java, kotlin, scala
+        /// and groovy; defaults to java,kotlin
+        #[arg(long, value_name = "LANGUAGES", value_delimiter = ',')]
+        lang: Vec<String>,
+
+        /// Optional encoding of the source files: utf8 (default), where invalid bytes are
+        /// replaced with a warning, or latin1 for ISO-8859-1 sources
+        #[arg(long, value_name = "ENCODING")]
+        encoding: Option<String>,
+
+        /// Skip test sources, i.e. files below a src/test folder
+        #[arg(long, conflicts_with = "separate_tests")]
+        exclude_tests: bool,
+
+        /// Prefix the packages of test sources (files below a src/test folder) with "[test] ",
+        /// keeping them apart from the main packages of the same name
+        #[arg(long)]
+        separate_tests: bool,
+
+        /// Graphviz output file name; defaults to "<prefix>.<ext>" for the selected format if not provided
+        #[arg(short, long, value_name = "OUTPUT_FILE_NAME")]
+        graph_out: Option<String>,
+
+        /// Optional file to write the generated dot source to; if given without --graph-out,
+        /// no image is rendered and graphviz is not required
+        #[arg(long, value_name = "DOT_FILE_NAME")]
+        dot_out: Option<String>,
+
+        /// Optional root class prefix to use as starting point
+        #[arg(short, long, value_name = "ROOT_CLASS_PREFIX")]
+        class_prefix: Option<String>,
+
+        /// Optional number of edge levels to follow from the packages matching the root class
+        /// prefix; 0 shows only those packages
+        #[arg(short, long, value_name = "DEPTH")]
+        depth: Option<usize>,
+
+        /// Optional number of packages to include, expanding from the roots towards the most-connected packages first
+        #[arg(long, value_name = "N")]
+        node_budget: Option<usize>,
+
+        /// Optional maximum number of nodes; larger graphs are rejected instead of rendered
+        #[arg(long, value_name = "N")]
+        max_nodes: Option<usize>,
+
+        /// Only keep edges to packages found in the scanned folder, dropping external imports
+        #[arg(long)]
+        internal_only: bool,
+
+        /// Optional prefix the target of every edge must start with; together with
+        /// --class-prefix this scopes both ends of the graph
+        #[arg(long, value_name = "PREFIX")]
+        target_prefix: Option<String>,
+
+        /// Regex matched against whole package names; matching packages are removed
+        /// from the graph along with their edges. Can be given multiple times
+        #[arg(long, value_name = "PATTERN")]
+        exclude_package: Vec<String>,
+
+        /// Keep dependencies flowing through packages removed by --exclude-package by
+        /// linking their importers directly to the packages they import
+        #[arg(long, requires = "exclude_package")]
+        bridge: bool,
+
+        /// Optional rank direction: lr (default), rl, tb or bt, in any case
+        #[arg(short, long, value_name = "RANK_DIR", value_parser = parse_rank_dir)]
+        rank_dir: Option<RankDir>,
+
+        /// Optional image format: svg (default), svgz for gzip-compressed svg, png, pdf,
+        /// or dot for the laid-out dot source
+        #[arg(long, value_name = "IMAGE_FORMAT", alias = "output-format")]
+        image_format: Option<String>,
+
+        /// Graphviz dot executable used to render images; defaults to "dot"
+        #[arg(long, value_name = "PATH", env = "DOT_BIN")]
+        dot_bin: Option<String>,
+
+        /// Optional graph format: dot (default, rendered with graphviz), html, a page around
+        /// the rendered svg highlighting the neighbors of a clicked node, mermaid, graphml or
+        /// csv, which write the graph text (graph.mmd, graph.graphml or graph.csv unless -g
+        /// is given) instead of an image, or tree, which prints an indented text tree unless
+        /// -g is given
+        #[arg(long, value_name = "FORMAT")]
+        format: Option<String>,
+
+        /// Optional root package to render the dominator tree of instead of the dependency graph
+        #[arg(long, value_name = "ROOT")]
+        dominators: Option<String>,
+
+        /// Lowercase imports of external packages so casing variants merge into one node
+        #[arg(long)]
+        normalize_external_case: bool,
+
+        /// Color the packages of each dependency cycle (strongly connected component) alike
+        #[arg(long)]
+        annotate_scc: bool,
+
+        /// Only keep edges whose target starts with this prefix; may be repeated
+        #[arg(long, value_name = "PREFIX")]
+        keep_edges_to: Vec<String>,
+
+        /// Optional regex whose first capture group defines the node a package belongs to,
+        /// e.g. '(com\.acme\.[^.]+)'; edges within one node are dropped
+        #[arg(long, value_name = "REGEX")]
+        node_key: Option<String>,
+
+        /// Optional wildcard import resolution; "strict" replaces wildcard imports of scanned
+        /// packages with the classes a file uses when exactly one package declares them
+        #[arg(long, value_name = "MODE")]
+        resolve_wildcards: Option<String>,
+
+        /// Optional git date (e.g. "3 months ago") since when to color packages by how many commits touched them
+        #[arg(long, value_name = "DURATION")]
+        churn_since: Option<String>,
+
+        /// Only keep edges between different top-level packages, taken as the first
+        /// SEGMENTS segments of each package name (2 if no value is given)
+        #[arg(long, value_name = "SEGMENTS", num_args = 0..=1, default_missing_value = "2")]
+        top_level_only: Option<usize>,
+
+        /// Render one graph per dependency cycle (strongly connected component) into the
+        /// output directory, named after the cycle's alphabetically smallest package
+        #[arg(long)]
+        split_by_scc: bool,
+
+        /// Render one graph per group of packages sharing their first N segments into the
+        /// output directory, named after that prefix; imports from outside the group are
+        /// drawn as one folder node per prefix they fall under
+        #[arg(long, value_name = "N", conflicts_with = "split_by_scc")]
+        split_by_prefix: Option<usize>,
+
+        /// Print every parsed file as a JSON line on stdout while the folder is still being scanned
+        #[arg(long)]
+        stream_parse: bool,
+
+        /// Add a tooltip to every package node listing the source files it is defined in
+        #[arg(long)]
+        tooltips: bool,
+
+        /// Optional JSON file to write every edge to together with the file and line of each
+        /// import creating it, with paths relative to the scanned folder or archive
+        #[arg(long, value_name = "FILE")]
+        edge_provenance: Option<String>,
+
+        /// Print how many nodes and edges each depth limit would include, without rendering
+        #[arg(long)]
+        budget_report: bool,
+
+        /// Scan and filter, then print the number of packages and edges, the size of the graph
+        /// after filtering and its root packages, without generating or rendering it
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Print the number of packages, edges, root packages and edges to external
+        /// imports as a line of JSON on stdout, besides generating the graph
+        #[arg(long)]
+        stats_json: bool,
+
+        /// Render the image even if it exists and was rendered from the same dot source,
+        /// as recorded in the .hash file next to it
+        #[arg(long)]
+        force: bool,
+
+        /// Optional number of seconds graphviz may take to render the graph before it is stopped
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+
+        /// Label every package with the number of distinct external libraries it depends on
+        #[arg(long)]
+        annotate_external_counts: bool,
+
+        /// Directory to write multiple graphs to; defaults to the current directory
+        #[arg(long, value_name = "DIR")]
+        output_dir: Option<String>,
+
+        /// Reverse every edge so arrows point from a package to the packages importing it
+        #[arg(long)]
+        reverse: bool,
+
+        /// Don't fill nodes with a color derived from their top-level namespace
+        #[arg(long)]
+        no_color: bool,
+
+        /// Optional number of leading package segments to group nodes into labeled clusters by
+        #[arg(long, value_name = "SEGMENTS")]
+        cluster_depth: Option<usize>,
+
+        /// Background color of the graph, as a graphviz color name or hex value; defaults to black
+        #[arg(long, value_name = "COLOR")]
+        bg_color: Option<String>,
+
+        /// Color of the edges, as a graphviz color name or hex value; defaults to white
+        #[arg(long, value_name = "COLOR")]
+        edge_color: Option<String>,
+
+        /// Optional fill color of every node, as a graphviz color name or hex value;
+        /// replaces the per-namespace colors
+        #[arg(long, value_name = "COLOR")]
+        node_color: Option<String>,
+
+        /// Graphviz graph attribute as key=value, e.g. ratio=compress, overriding the
+        /// built-in ones; may be repeated
+        #[arg(long, value_name = "KEY=VALUE")]
+        graph_attr: Vec<String>,
+
+        /// Graphviz attribute as key=value applied to every node, e.g. fontname=Helvetica,
+        /// overriding the built-in ones; may be repeated
+        #[arg(long, value_name = "KEY=VALUE")]
+        node_attr: Vec<String>,
+
+        /// Graphviz attribute as key=value applied to every edge, e.g. arrowsize=0.5,
+        /// overriding the built-in ones; may be repeated
+        #[arg(long, value_name = "KEY=VALUE")]
+        edge_attr: Vec<String>,
+
+        /// Optional number of leading segments to collapse every package and import to,
+        /// e.g. 2 merges com.a.x -> com.b.y into com.a -> com.b
+        #[arg(long, value_name = "SEGMENTS")]
+        collapse_depth: Option<usize>,
+
+        /// Optional node granularity: class (default) keeps imports as written, package
+        /// maps every import to the package it belongs to so edges land on package nodes
+        #[arg(long, value_name = "GRANULARITY")]
+        granularity: Option<String>,
+
+        /// Merge imports of nested types into their outer class, e.g. com.foo.Outer.Inner
+        /// into com.foo.Outer, telling classes from packages by their capitalization
+        #[arg(long)]
+        merge_nested: bool,
+
+        /// Label every edge with the number of imports behind it, summed when packages or
+        /// nodes are collapsed, and draw heavier edges wider
+        #[arg(long)]
+        weighted: bool,
+
+        /// Optional progress display on stderr while files are parsed: auto (default) shows it
+        /// when stdout is a terminal, always or never; --quiet hides it
+        #[arg(long, value_name = "WHEN")]
+        progress: Option<String>,
+
+        /// Fail when a file can't be read or has a duplicate or malformed package declaration
+        /// or import, instead of only warning; files reused from --cache aren't checked again
+        #[arg(long)]
+        strict: bool,
+
+        /// Optional text encoding of the mermaid, graphml, tree and csv formats and of the
+        /// --edge-provenance file: utf8 (default) or utf8-bom to prepend a byte order mark
+        #[arg(long, value_name = "ENCODING")]
+        output_encoding: Option<String>,
+
+        /// Optional line ending of the mermaid, graphml, tree and csv formats and of the
+        /// --edge-provenance file: lf (default) or crlf
+        #[arg(long, value_name = "LINE_ENDING")]
+        line_ending: Option<String>,
+
+        /// Optional TOML file with defaults for these options, keyed by their names with
+        /// underscores (e.g. rank_dir = "tb"); ./jadep.toml is used when present
+        #[arg(long, value_name = "FILE")]
+        config: Option<String>,
+    },
+
+    /// Export the dependency graph of a folder of java or kotlin files as JSON
+    Json {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional JSON output file name; written to stdout if not provided
+        #[arg(short, long, value_name = "OUTPUT_FILE_NAME")]
+        out: Option<String>,
+
+        /// Optional root class prefix to use as starting point
+        #[arg(short, long, value_name = "ROOT_CLASS_PREFIX")]
+        class_prefix: Option<String>,
+
+        /// Optional number of edge levels to follow from the packages matching the root class
+        /// prefix; 0 shows only those packages
+        #[arg(short, long, value_name = "DEPTH")]
+        depth: Option<usize>,
+
+        /// Optional text encoding: utf8 (default) or utf8-bom to prepend a byte order mark
+        #[arg(long, value_name = "ENCODING")]
+        output_encoding: Option<String>,
+
+        /// Optional line ending: lf (default) or crlf
+        #[arg(long, value_name = "LINE_ENDING")]
+        line_ending: Option<String>,
+    },
+
+    /// Report dependency cycles between packages, exiting with status 2 if any exist
+    Cycles {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional package prefix to limit the analysis to
+        #[arg(short, long, value_name = "ROOT_CLASS_PREFIX")]
+        class_prefix: Option<String>,
+    },
+
+    /// Print packages in build order, dependencies before the packages using them
+    Toposort {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional package prefix to limit the analysis to
+        #[arg(short, long, value_name = "ROOT_CLASS_PREFIX")]
+        class_prefix: Option<String>,
+    },
+
+    /// Print the packages no other scanned package imports, e.g. candidates for deletion
+    Roots {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional package prefix to limit the analysis to
+        #[arg(short, long, value_name = "ROOT_CLASS_PREFIX")]
+        class_prefix: Option<String>,
+    },
+
+    /// Print the packages importing no other scanned package; external imports don't count
+    Leaves {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional package prefix to limit the analysis to
+        #[arg(short, long, value_name = "ROOT_CLASS_PREFIX")]
+        class_prefix: Option<String>,
+    },
+
+    /// Print the packages neither importing nor imported by any other scanned package,
+    /// e.g. candidates for deletion, telling truly isolated ones from those only
+    /// importing external dependencies
+    Orphans {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional package prefix to limit the analysis to
+        #[arg(short, long, value_name = "ROOT_CLASS_PREFIX")]
+        class_prefix: Option<String>,
+    },
+
+    /// Print the longest chain of package dependencies, with cycles condensed into one step
+    LongestPath {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional package prefix to limit the analysis to
+        #[arg(short, long, value_name = "ROOT_CLASS_PREFIX")]
+        class_prefix: Option<String>,
+    },
+
+    /// Print a shortest chain of package dependencies leading from one package to another
+    Path {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Package the chain starts at
+        #[arg(long, value_name = "PACKAGE")]
+        from: String,
+
+        /// Package the chain ends at
+        #[arg(long, value_name = "PACKAGE")]
+        to: String,
+
+        /// Optional svg file to render the package graph to, with the chain highlighted
+        #[arg(short, long, value_name = "OUTPUT_FILE_NAME")]
+        graph_out: Option<String>,
+
+        /// Graphviz dot executable used to render images
+        #[arg(long, value_name = "PATH", env = "DOT_BIN", default_value = "dot")]
+        dot_bin: String,
+    },
+
+    /// Compare two dependency graphs, e.g. before and after a branch, printing the added
+    /// (+) and removed (-) edges and the newly introduced cycles, exiting with status 2
+    /// if there are any of the latter
+    Diff {
+        /// Old folder, jar or zip archive, or a JSON file exported by the json subcommand
+        #[arg(long, value_name = "PATH")]
+        old: String,
+
+        /// New folder, jar or zip archive, or a JSON file exported by the json subcommand
+        #[arg(long, value_name = "PATH")]
+        new: String,
+
+        /// Optional svg file to render the combined graph to, with added edges in green
+        /// and removed ones dashed in red
+        #[arg(short, long, value_name = "OUTPUT_FILE_NAME")]
+        graph_out: Option<String>,
+
+        /// Graphviz dot executable used to render images
+        #[arg(long, value_name = "PATH", env = "DOT_BIN", default_value = "dot")]
+        dot_bin: String,
+    },
+
+    /// Print every package a package depends on, directly or not, sorted
+    Closure {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Package to start from
+        #[arg(long, value_name = "PACKAGE")]
+        from: String,
+
+        /// Optional number of edge levels to follow from the package; 0 shows only the package
+        #[arg(short, long, value_name = "DEPTH")]
+        depth: Option<usize>,
+
+        /// Optional svg file to render the package and everything it depends on to
+        #[arg(short, long, value_name = "OUTPUT_FILE_NAME")]
+        graph_out: Option<String>,
+
+        /// Graphviz dot executable used to render images
+        #[arg(long, value_name = "PATH", env = "DOT_BIN", default_value = "dot")]
+        dot_bin: String,
+    },
+
+    /// Report pairs of packages with similar imports as potential merge candidates
+    Similar {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Minimum Jaccard similarity (0.0 to 1.0) of two import sets to report the pair
+        #[arg(short, long, value_name = "THRESHOLD", default_value_t = 0.8)]
+        threshold: f64,
+    },
+
+    /// Report packages that are both widely depended upon and widely coupled
+    Hotspots {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Minimum number of scanned packages depending on a package for it to be reported
+        #[arg(long, value_name = "COUNT", default_value_t = 3)]
+        min_in: usize,
+
+        /// Minimum number of scanned packages a package depends on for it to be reported
+        #[arg(long, value_name = "COUNT", default_value_t = 3)]
+        min_out: usize,
+    },
+
+    /// Tally the imports pointing outside the scanned packages by namespace root
+    Externals {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Number of leading segments forming a namespace root, e.g. 2 for org.springframework
+        #[arg(long, value_name = "SEGMENTS", default_value_t = 2)]
+        segments: usize,
+    },
+
+    /// Print the fan-in, fan-out and instability of every package
+    Metrics {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional column to sort by, highest first: fan-in (default), fan-out or instability
+        #[arg(long, value_name = "COLUMN")]
+        sort_by: Option<String>,
+
+        /// Instead of the table, list the packages above --max-fan-in or --max-fan-out,
+        /// furthest over a threshold first, with a suggestion for each
+        #[arg(long, conflicts_with = "sort_by")]
+        advise: bool,
+
+        /// Optional number of importing packages above which a package is flagged; defaults to 10
+        #[arg(long, value_name = "N", requires = "advise")]
+        max_fan_in: Option<usize>,
+
+        /// Optional number of distinct imports above which a package is flagged; defaults to 20
+        #[arg(long, value_name = "N", requires = "advise")]
+        max_fan_out: Option<usize>,
+    },
+
+    /// List wildcard imports, which hide the specific classes a file depends on
+    Wildcards {
+        /// Path to folder containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Exit with status 2 if any wildcard import is found
+        #[arg(long)]
+        strict: bool,
+
+        /// Glob of paths to skip while scanning, e.g. "**/build/**"; may be repeated
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Skip paths ignored by .gitignore files in the scanned folders
+        #[arg(long)]
+        gitignore: bool,
+
+        /// Descend into symbolic links to folders; each folder is still scanned only once,
+        /// so links back to an ancestor don't loop
+        #[arg(long)]
+        follow_symlinks: bool,
+
+        /// Comma-separated languages whose source files are parsed: java, kotlin, scala
+        /// and groovy; defaults to java,kotlin
+        #[arg(long, value_name = "LANGUAGES", value_delimiter = ',')]
+        lang: Vec<String>,
+    },
+
+    /// Report the edges breaking the rules of a rules file, exiting with status 2 if any exist
+    CheckRules {
+        /// Path to a folder, or a jar or zip archive, containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// File of forbidden dependencies, one "SOURCE -> TARGET" rule of package
+        /// prefixes per line, e.g. "com.acme.web -> com.acme.persistence"
+        #[arg(short, long, value_name = "FILE")]
+        rules: String,
+    },
+
+    /// Report java files whose package declaration doesn't match their folder, exiting
+    /// with status 2 if any exist
+    Lint {
+        /// Path to folder containing java or kotlin files
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Folder the package folders start from, e.g. src/main/java; defaults to the path
+        #[arg(long, value_name = "PATH")]
+        source_root: Option<String>,
+
+        /// Glob of paths to skip while scanning, e.g. "**/build/**"; may be repeated
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Skip paths ignored by .gitignore files in the scanned folders
+        #[arg(long)]
+        gitignore: bool,
+
+        /// Descend into symbolic links to folders; each folder is still scanned only once,
+        /// so links back to an ancestor don't loop
+        #[arg(long)]
+        follow_symlinks: bool,
+
+        /// Comma-separated languages whose source files are parsed: java, kotlin, scala
+        /// and groovy; defaults to java,kotlin
+        #[arg(long, value_name = "LANGUAGES", value_delimiter = ',')]
+        lang: Vec<String>,
+    },
+
+    /// Regenerate a graph whenever a source file below a folder changes, until interrupted
+    Watch {
+        /// Path to the folder containing java or kotlin files to watch and graph
+        #[arg(short, long, value_name = "PATH")]
+        path: String,
+
+        /// Optional number of milliseconds without changes to wait for before
+        /// regenerating, so bulk edits only trigger one run; defaults to 300
+        #[arg(long, value_name = "MS")]
+        debounce: Option<u64>,
+
+        /// Options of the graph command to regenerate with, given after "--", e.g.
+        /// -- --graph-out deps.svg; a parse cache in the temporary folder is used unless
+        /// --cache is given, so only changed files are parsed again
+        #[arg(last = true, value_name = "GRAPH_OPTIONS")]
+        graph_options: Vec<String>,
+    },
+}
+
+// Function to parse a graphviz attribute given as key=value, rejecting keys graphviz
+// wouldn't accept unquoted
+fn parse_dot_attribute(
+    option: &str,
+    attribute: &str,
+) -> anyhow::Result<(String, String)> {
+    match attribute.split_once('=') {
+        Some((key, value))
+            if key.chars().next().is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+            Ok((key.to_string(), value.to_string()))
+        }
+        _ => bail!("invalid {} \"{}\"; expected key=value, e.g. ratio=compress", option, attribute),
+    }
+}
+
+// Function to run a subcommand, returning the exit code to finish with:
+// 0 on success and 2 when a check found issues, errors exit with 1
+pub(crate) fn run(
+    command: Commands,
+    parse_options: &ParseOptions,
+) -> anyhow::Result<ExitCode> {
+    match command {
+        Commands::Graph {
+            path,
+            files_from,
+            cache,
+            layout,
+            build_system,
+            targets,
+            exclude,
+            gitignore,
+            follow_symlinks,
+            modules,
+            lang,
+            encoding,
+            exclude_tests,
+            separate_tests,
+            graph_out,
+            dot_out,
+            class_prefix,
+            depth,
+            node_budget,
+            max_nodes,
+            internal_only,
+            target_prefix,
+            exclude_package,
+            bridge,
+            rank_dir,
+            image_format,
+            dot_bin,
+            format,
+            dominators,
+            normalize_external_case: normalize_external,
+            annotate_scc,
+            keep_edges_to: target_prefixes,
+            node_key,
+            resolve_wildcards,
+            churn_since,
+            top_level_only,
+            split_by_scc,
+            split_by_prefix,
+            stream_parse,
+            tooltips,
+            edge_provenance,
+            budget_report,
+            dry_run,
+            stats_json,
+            force,
+            timeout,
+            annotate_external_counts,
+            output_dir,
+            reverse,
+            no_color,
+            cluster_depth,
+            bg_color,
+            edge_color,
+            node_color,
+            graph_attr,
+            node_attr,
+            edge_attr,
+            collapse_depth,
+            granularity,
+            merge_nested,
+            weighted,
+            progress,
+            strict,
+            output_encoding,
+            line_ending,
+            config,
+        } => {
+            let config = GraphConfig::load(config.as_deref())?;
+
+            // options given on the command line take precedence over the config file
+            let layout = layout.or(config.layout);
+            let build_system = build_system.or(config.build_system);
+            let targets = targets.or(config.targets);
+            let exclude = if exclude.is_empty() { config.exclude } else { exclude };
+            let gitignore = gitignore || config.gitignore;
+            let follow_symlinks = follow_symlinks || config.follow_symlinks;
+            let modules = modules || config.modules;
+            let lang = if lang.is_empty() { config.lang } else { lang };
+            let encoding = encoding.or(config.encoding);
+            let exclude_tests = exclude_tests || config.exclude_tests;
+            let separate_tests = separate_tests || config.separate_tests;
+
+            if exclude_tests && separate_tests {
+                bail!("--exclude-tests and --separate-tests can't be combined");
+            }
+            let cache = cache.or(config.cache);
+            let class_prefix = class_prefix.or(config.class_prefix);
+            let depth = depth.or(config.depth);
+            let node_budget = node_budget.or(config.node_budget);
+            let max_nodes = max_nodes.or(config.max_nodes);
+            let internal_only = internal_only || config.internal_only;
+            let target_prefix = target_prefix.or(config.target_prefix);
+            let exclude_package = if exclude_package.is_empty() { config.exclude_package } else { exclude_package };
+            let bridge = bridge || config.bridge;
+            let rank_dir =
+                match rank_dir {
+                    Some(rank_dir) => rank_dir,
+                    None => parse_rank_dir(config.rank_dir.as_deref().unwrap_or("lr"))
+                        .map_err(anyhow::Error::msg)?,
+                };
+            let image_format = image_format.or(config.image_format);
+            let dot_bin = dot_bin.or(config.dot_bin).unwrap_or("dot".to_string());
+            let timeout = timeout.or(config.timeout).map(Duration::from_secs);
+            let format = format.or(config.format);
+            let normalize_external = normalize_external || config.normalize_external_case;
+            let annotate_scc = annotate_scc || config.annotate_scc;
+            let target_prefixes = if target_prefixes.is_empty() { config.keep_edges_to } else { target_prefixes };
+            let node_key = node_key.or(config.node_key);
+            let resolve_wildcards = resolve_wildcards.or(config.resolve_wildcards);
+            let top_level_only = top_level_only.or(config.top_level_only);
+            let tooltips = tooltips || config.tooltips;
+            let annotate_external_counts = annotate_external_counts || config.annotate_external_counts;
+            let output_dir = output_dir.or(config.output_dir);
+            let reverse = reverse || config.reverse;
+            let no_color = no_color || config.no_color;
+            let cluster_depth = cluster_depth.or(config.cluster_depth);
+            let bg_color = bg_color.or(config.bg_color).unwrap_or("black".to_string());
+            let edge_color = edge_color.or(config.edge_color).unwrap_or("white".to_string());
+            let node_color = node_color.or(config.node_color);
+            let graph_attr = if graph_attr.is_empty() { config.graph_attr } else { graph_attr };
+            let node_attr = if node_attr.is_empty() { config.node_attr } else { node_attr };
+            let edge_attr = if edge_attr.is_empty() { config.edge_attr } else { edge_attr };
+            let collapse_depth = collapse_depth.or(config.collapse_depth);
+            let granularity = granularity.or(config.granularity);
+            let merge_nested = merge_nested || config.merge_nested;
+            let weighted = weighted || config.weighted;
+            let progress = progress.or(config.progress);
+            let strict = strict || config.strict;
+            let output_encoding = output_encoding.or(config.output_encoding);
+            let line_ending = line_ending.or(config.line_ending);
+
+            let paths =
+                if path.is_empty() {
+                    vec![".".to_string()]
+                } else {
+                    path
+                };
+
+            let folder_paths = paths
+                .iter()
+                .map(|path| input_path(path))
+                .collect::<anyhow::Result<Vec<&Path>>>()?;
+            let root_class_prefix = class_prefix;
+
+            let listed_files = files_from
+                .map(|files_from| {
+                    read_file_list(&files_from)
+                        .with_context(|| format!("could not read file list {}", files_from))
+                })
+                .transpose()?;
+
+            let listed_files =
+                match build_system {
+                    Some(build_system) => {
+                        let Ok(BuildSystem::Bazel) = BuildSystem::from_str(build_system.as_str()) else {
+                            bail!("unsupported build system; expected bazel");
+                        };
+
+                        // the first path is the workspace the labels are relative to
+                        let workspace = folder_paths[0];
+
+                        let labels =
+                            match listed_files {
+                                Some(listed_files) => listed_files
+                                    .iter()
+                                    .map(|listed_file| listed_file.to_string_lossy().to_string())
+                                    .collect(),
+                                None => query_bazel_sources(workspace, targets.as_deref().unwrap_or("//..."))?,
+                            };
+
+                        Some(resolve_bazel_labels(workspace, &labels))
+                    }
+                    None => listed_files,
+                };
+
+            let graph_style = GraphStyle {
+                rank_dir,
+                color_nodes: !no_color,
+                cluster_depth,
+                bg_color,
+                edge_color,
+                node_color,
+                extra_graph_attributes: graph_attr
+                    .iter()
+                    .map(|attribute| parse_dot_attribute("--graph-attr", attribute))
+                    .collect::<anyhow::Result<_>>()?,
+                extra_node_attributes: node_attr
+                    .iter()
+                    .map(|attribute| parse_dot_attribute("--node-attr", attribute))
+                    .collect::<anyhow::Result<_>>()?,
+                extra_edge_attributes: edge_attr
+                    .iter()
+                    .map(|attribute| parse_dot_attribute("--edge-attr", attribute))
+                    .collect::<anyhow::Result<_>>()?,
+            };
+
+            let Ok(image_format) =
+                ImageFormat::from_str(
+                    image_format.unwrap_or("svg".to_string()).as_str(),
+                ) else {
+                bail!("unsupported image format; expected svg, svgz, png, pdf or dot");
+            };
+
+            let Ok(graph_format) =
+                GraphFormat::from_str(
+                    format.unwrap_or("dot".to_string()).as_str(),
+                ) else {
+                bail!("unsupported graph format; expected dot, mermaid, graphml, tree, csv or html");
+            };
+
+            if graph_format != GraphFormat::Dot && (dot_out.is_some() || split_by_scc) {
+                bail!("--dot-out and --split-by-scc only apply to the dot format");
+            }
+
+            if collapse_depth == Some(0) {
+                bail!("--collapse-depth must be at least 1");
+            }
+
+            let Ok(granularity) =
+                Granularity::from_str(
+                    granularity.unwrap_or("class".to_string()).as_str(),
+                ) else {
+                bail!("unsupported granularity; expected class or package");
+            };
+
+            let text_encoding = TextEncoding::parse(output_encoding, line_ending)?;
+
+            let output_extension =
+                match graph_format {
+                    GraphFormat::Dot => image_format.extension(),
+                    GraphFormat::Mermaid => "mmd",
+                    GraphFormat::Graphml => "graphml",
+                    GraphFormat::Tree => "txt",
+                    GraphFormat::Csv => "csv",
+                    GraphFormat::Html => "html",
+                };
+
+            // the text tree is meant for the terminal unless a file is asked for
+            let print_graph = graph_format == GraphFormat::Tree && graph_out.is_none();
+
+            // graphviz is only needed when an image is wanted, which is always
+            // the case unless just the dot source was asked for
+            let render_image = graph_out.is_some() || dot_out.is_none();
+
+            let svg_file_path =
+                if let Some(graph_out) = graph_out {
+                    let graph_out = Path::new(graph_out.as_str()).to_path_buf();
+                    warn_extension_mismatch(&graph_out, output_extension);
+                    graph_out
+                } else {
+                    default_output_path(
+                        root_class_prefix.as_deref(),
+                        output_extension,
+                    )
+                };
+
+            let node_key = node_key
+                .map(|node_key| {
+                    match regex::Regex::new(node_key.as_str()) {
+                        Ok(node_key) if node_key.captures_len() > 1 => Ok(node_key),
+                        Ok(_) => {
+                            bail!("node key regex \"{}\" has no capture group", node_key);
+                        }
+                        Err(error) => {
+                            bail!("invalid node key regex: {}", error);
+                        }
+                    }
+                })
+                .transpose()?;
+
+            let exclude_packages = exclude_package
+                .iter()
+                .map(|pattern| {
+                    regex::Regex::new(format!("^(?:{})$", pattern).as_str())
+                        .with_context(|| format!("invalid package pattern \"{}\"", pattern))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let scan_roots: Vec<PathBuf> =
+                if let Some(layout) = layout {
+                    let Ok(layout) = Layout::from_str(layout.as_str()) else {
+                        bail!("unsupported layout; expected maven or gradle");
+                    };
+
+                    let source_roots: Vec<(PathBuf, SourceSet)> = folder_paths
+                        .iter()
+                        .flat_map(|folder_path| discover_source_roots(folder_path, layout))
+                        .collect();
+
+                    info!("Found {} source roots", source_roots.len());
+
+                    for (source_root, source_set) in &source_roots {
+                        info!("  {} ({})", source_root.display(), source_set.name());
+                    }
+
+                    source_roots
+                        .into_iter()
+                        .map(|(source_root, _)| source_root)
+                        .collect()
+                } else {
+                    folder_paths
+                        .iter()
+                        .map(|folder_path| folder_path.to_path_buf())
+                        .collect()
+                };
+
+
+            let Ok(encoding) =
+                SourceEncoding::from_str(
+                    encoding.unwrap_or("utf8".to_string()).as_str(),
+                ) else {
+                bail!("unsupported source encoding; expected utf8 or latin1");
+            };
+
+            let walk_filter = WalkFilter {
+                exclude: Arc::new(exclude_globs(&exclude)?),
+                respect_gitignore: gitignore,
+                gitignores: Vec::new(),
+                modules,
+                exclude_tests,
+                separate_tests,
+                languages: parse_languages(&lang)?,
+                parse_options: ParseOptions {
+                    encoding,
+                    problems: Arc::default(),
+                    ..parse_options.clone()
+                },
+                follow_symlinks,
+                visited_folders: Arc::default(),
+            };
+
+            let imports_map = DashMap::<String, Vec<String>>::new();
+            let parse_cache = cache.as_ref().map(|cache| ParseCache::load(Path::new(cache), &walk_filter.fingerprint()));
+
+            let Ok(progress_mode) =
+                ProgressMode::from_str(
+                    progress.unwrap_or("auto".to_string()).as_str(),
+                ) else {
+                bail!("unsupported progress mode; expected auto, always or never");
+            };
+
+            // files are counted as they are parsed, since the total isn't known before the walk
+            let progress = progress_mode.is_shown().then(|| {
+                let progress = ProgressBar::new_spinner();
+                progress.set_style(
+                    ProgressStyle::with_template("{spinner} {pos} files parsed ({elapsed})")
+                        .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+                );
+                progress.enable_steady_tick(std::time::Duration::from_millis(100));
+                progress
+            });
+
+            let count_parsed = || {
+                if let Some(ref progress) = progress {
+                    progress.inc(1);
+                }
+            };
+
+            // a file list replaces the folder walk, e.g. for only the files changed in CI
+            let parse_sources = |parsed_files: Option<&Sender<ParsedFile>>| -> anyhow::Result<()> {
+                if let Some(ref listed_files) = listed_files {
+                    merge_imports_maps(&imports_map, parse_listed_files(listed_files, parsed_files, &walk_filter, parse_cache.as_ref(), Some(&count_parsed)));
+                } else {
+                    for scan_root in &scan_roots {
+                        let scanned =
+                            if is_archive(scan_root) {
+                                parse_archive(scan_root, parsed_files, &walk_filter, Some(&count_parsed))?
+                            } else {
+                                traverse_folder_par(scan_root.clone(), parsed_files, &walk_filter, parse_cache.as_ref(), Some(&count_parsed))
+                            };
+
+                        merge_imports_maps(&imports_map, scanned);
+                    }
+                }
+
+                Ok(())
+            };
+
+            // the parsed files are kept for the options that look back at every file
+            let keep_parsed_files =
+                tooltips || edge_provenance.is_some() || resolve_wildcards.is_some() || churn_since.is_some();
+
+            let parsed_files =
+                if stream_parse || keep_parsed_files {
+                    let (sender, receiver) = mpsc::channel::<ParsedFile>();
+
+                    // a single writer keeps every json line intact while files are parsed in parallel
+                    let writer = std::thread::spawn(move || {
+                        let mut stdout = std::io::stdout().lock();
+                        let mut parsed_files = Vec::new();
+
+                        for parsed_file in receiver {
+                            if stream_parse {
+                                let line = serde_json::to_string(&parsed_file).unwrap();
+                                let _ = writeln!(stdout, "{}", line).and_then(|_| stdout.flush());
+                            }
+
+                            if keep_parsed_files {
+                                parsed_files.push(parsed_file);
+                            }
+                        }
+
+                        parsed_files
+                    });
+
+                    let parsed = parse_sources(Some(&sender));
+
+                    drop(sender);
+                    let parsed_files = writer.join().unwrap();
+
+                    parsed?;
+                    parsed_files
+                } else {
+                    parse_sources(None)?;
+                    Vec::new()
+                };
+
+            if let Some(ref progress) = progress {
+                progress.finish_and_clear();
+            }
+
+            let parse_problems = walk_filter.parse_options.problems.load(Ordering::Relaxed);
+
+            if strict && parse_problems > 0 {
+                bail!("found {} parse problems, see the warnings above", parse_problems);
+            }
+
+            if let (Some(cache), Some(parse_cache)) = (&cache, &parse_cache) {
+                debug!(
+                    "Reused {} cached files, parsed {}",
+                    parse_cache.reused.load(Ordering::Relaxed),
+                    parse_cache.parsed.load(Ordering::Relaxed),
+                );
+
+                if let Err(error) = parse_cache.save(Path::new(cache)) {
+                    warn!("could not write cache {}: {}", cache, error);
+                }
+            }
+
+            if let Some(ref edge_provenance) = edge_provenance {
+                let edges = collect_edge_provenance(&parsed_files, &scan_roots);
+                let json_content = serde_json::to_string_pretty(&edges)? + "\n";
+
+                write_text_output(&json_content, Some(Path::new(edge_provenance)), text_encoding)?;
+            }
+
+            let mut package_files = BTreeMap::<String, Vec<String>>::new();
+
+            if tooltips {
+                for parsed_file in &parsed_files {
+                    package_files.entry(parsed_file.package.clone()).or_default().push(parsed_file.file.clone());
+                }
+            }
+
+            if let Some(resolve_wildcards) = resolve_wildcards {
+                if resolve_wildcards != "strict" {
+                    bail!("unsupported wildcard resolution; expected strict");
+                }
+
+                let ambiguities = resolve_wildcard_imports(&parsed_files, &imports_map);
+
+                for (file, class_name, candidates) in &ambiguities {
+                    warn!(
+                        "{}: {} is ambiguous between {}",
+                        file,
+                        class_name,
+                        candidates.join(", "),
+                    );
+                }
+            }
+
+            info!("Found {} packages", imports_map.len());
+
+            // every edge is renamed along with the map, so merged edges sum their weights
+            let mut edge_weights = weighted.then(|| count_edge_weights(&imports_map));
+
+            if merge_nested {
+                edge_weights = edge_weights.map(|edge_weights| {
+                    rename_edge_weights(
+                        &edge_weights,
+                        |package_name| package_name.to_string(),
+                        |import_value| outer_type(import_value).to_string(),
+                    )
+                });
+
+                merge_nested_imports(&imports_map);
+            }
+
+            if granularity == Granularity::Package {
+                let internal_packages: HashSet<String> = imports_map
+                    .iter()
+                    .map(|entry| entry.key().to_string())
+                    .collect();
+
+                edge_weights = edge_weights.map(|edge_weights| {
+                    rename_edge_weights(
+                        &edge_weights,
+                        |package_name| package_name.to_string(),
+                        |import_value| import_package(import_value, &internal_packages).to_string(),
+                    )
+                });
+
+                collapse_imports_to_packages(&imports_map);
+            }
+
+            let imports_map =
+                if let Some(ref node_key) = node_key {
+                    edge_weights = edge_weights.map(|edge_weights| {
+                        rename_edge_weights(
+                            &edge_weights,
+                            |name| rekey_name(name, node_key),
+                            |name| rekey_name(name, node_key),
+                        )
+                    });
+
+                    package_files = rename_package_files(package_files, |name| rekey_name(name, node_key));
+
+                    rekey_imports_map(&imports_map, node_key)
+                } else {
+                    imports_map
+                };
+
+            let imports_map =
+                if let Some(segments) = collapse_depth {
+                    edge_weights = edge_weights.map(|edge_weights| {
+                        rename_edge_weights(
+                            &edge_weights,
+                            |name| package_prefix(name, segments).to_string(),
+                            |name| package_prefix(name, segments).to_string(),
+                        )
+                    });
+
+                    package_files =
+                        rename_package_files(package_files, |name| package_prefix(name, segments).to_string());
+
+                    collapse_imports_map(&imports_map, segments)
+                } else {
+                    imports_map
+                };
+
+            if normalize_external {
+                let internal_packages: HashSet<String> = imports_map
+                    .iter()
+                    .map(|entry| entry.key().to_string())
+                    .collect();
+
+                edge_weights = edge_weights.map(|edge_weights| {
+                    rename_edge_weights(
+                        &edge_weights,
+                        |package_name| package_name.to_string(),
+                        |import_value| {
+                            if is_internal(import_value, &internal_packages) {
+                                import_value.to_string()
+                            } else {
+                                import_value.to_lowercase()
+                            }
+                        },
+                    )
+                });
+
+                normalize_external_case(&imports_map);
+            }
+
+            if let Some(segments) = top_level_only {
+                drop_same_top_level_edges(&imports_map, segments);
+            }
+
+            if !target_prefixes.is_empty() {
+                keep_edges_to(&imports_map, &target_prefixes);
+            }
+
+            let imports_map =
+                if reverse {
+                    edge_weights = edge_weights.map(|edge_weights| {
+                        edge_weights
+                            .into_iter()
+                            .map(|((package_name, import_value), weight)| ((import_value, package_name), weight))
+                            .collect()
+                    });
+
+                    invert_imports_map(&imports_map)
+                } else {
+                    imports_map
+                };
+
+            let edge_attributes = edge_weights
+                .as_ref()
+                .map(weight_edge_attributes)
+                .unwrap_or_default();
+
+            if stats_json {
+                println!("{}", serde_json::to_string(&compute_graph_stats(&imports_map, root_class_prefix.as_deref()))?);
+            }
+
+            if budget_report {
+                // every option narrowing the rendered tree but the depth applies to the report
+                let tree_options = TreeOptions {
+                    node_budget,
+                    internal_only,
+                    target_prefix,
+                    exclude_packages,
+                    bridge,
+                    ..TreeOptions::default()
+                };
+
+                println!("depth  nodes  edges");
+
+                for (depth, (nodes, edges)) in
+                    depth_budget_report(&imports_map, root_class_prefix.as_deref(), &tree_options)
+                        .into_iter()
+                        .enumerate() {
+                    println!("{:<5}  {:<5}  {}", depth, nodes, edges);
+                }
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            if dry_run {
+                let tree_options = TreeOptions {
+                    depth,
+                    node_budget,
+                    internal_only,
+                    target_prefix,
+                    exclude_packages,
+                    bridge,
+                };
+
+                print!("{}", dry_run_summary(&imports_map, root_class_prefix.as_deref(), &tree_options));
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let mut node_attributes = HashMap::<String, Vec<String>>::new();
+
+            if annotate_scc {
+                let cyclic_components =
+                    find_strongly_connected_components(&imports_map)
+                        .into_iter()
+                        .filter(|component| component.len() > 1);
+
+                for (component_id, component) in cyclic_components.enumerate() {
+                    // spread hues by the golden ratio so neighbouring ids stay distinguishable
+                    let hue = (component_id as f64 * 0.618_033_988_75).fract();
+
+                    for package_name in component {
+                        node_attributes
+                            .entry(package_name)
+                            .or_default()
+                            .extend([
+                                format!("fillcolor=\"{:.3} 0.600 0.950\"", hue),
+                                format!("tooltip=\"SCC {}\"", component_id),
+                            ]);
+                    }
+                }
+            }
+
+            if annotate_external_counts {
+                for (package_name, libraries) in collect_external_libraries(&imports_map) {
+                    if libraries.is_empty() {
+                        continue;
+                    }
+
+                    let libraries: Vec<String> = libraries.into_iter().collect();
+
+                    node_attributes
+                        .entry(package_name.clone())
+                        .or_default()
+                        .extend([
+                            format!(
+                                "label={}",
+                                escape_dot_string(&format!("{}\n{} external", package_name, libraries.len())),
+                            ),
+                            format!("tooltip={}", escape_dot_string(&libraries.join(", "))),
+                        ]);
+                }
+            }
+
+            if let Some(ref churn_since) = churn_since {
+                // packages are named like the nodes they were renamed to above
+                let node_name = |package_name: &str| {
+                    let mut node_name = package_name.to_string();
+
+                    if let Some(ref node_key) = node_key {
+                        node_name = rekey_name(&node_name, node_key);
+                    }
+
+                    if let Some(segments) = collapse_depth {
+                        node_name = package_prefix(&node_name, segments).to_string();
+                    }
+
+                    node_name
+                };
+
+                let churn = count_package_churn(&parsed_files, &scan_roots, churn_since, node_name);
+                let max_churn = churn.values().copied().max().unwrap_or(0);
+
+                for (package_name, commits) in churn {
+                    // from pale yellow for rarely changed to saturated red for the most changed
+                    let ratio = commits as f64 / max_churn as f64;
+
+                    node_attributes
+                        .entry(package_name)
+                        .or_default()
+                        .extend([
+                            format!(
+                                "fillcolor=\"{:.3} {:.3} 1.000\"",
+                                0.15 * (1.0 - ratio),
+                                0.2 + 0.8 * ratio,
+                            ),
+                            format!("tooltip=\"{} commits\"", commits),
+                        ]);
+                }
+            }
+
+            for (package_name, files) in &package_files {
+                let attributes = node_attributes.entry(package_name.clone()).or_default();
+
+                // annotations that already explain the node keep their own tooltip
+                if !attributes.iter().any(|attribute| attribute.starts_with("tooltip=")) {
+                    attributes.push(files_tooltip(files));
+                }
+            }
+
+            if split_by_scc {
+                let output_dir = PathBuf::from(output_dir.unwrap_or(".".to_string()));
+                fs::create_dir_all(&output_dir)
+                    .with_context(|| format!("could not create {}", output_dir.display()))?;
+
+                let cyclic_components: Vec<Vec<String>> =
+                    find_strongly_connected_components(&imports_map)
+                        .into_iter()
+                        .filter(|component| component.len() > 1)
+                        .collect();
+
+                info!("Found {} dependency cycles", cyclic_components.len());
+
+                for component in cyclic_components {
+                    let members: HashSet<&String> = component.iter().collect();
+                    let component_map = DashMap::<String, Vec<String>>::new();
+
+                    for package_name in &component {
+                        let imports = imports_map
+                            .get(package_name)
+                            .map(|imports| {
+                                imports
+                                    .iter()
+                                    .filter(|import_value| members.contains(import_value))
+                                    .cloned()
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+
+                        component_map.insert(package_name.clone(), imports);
+                    }
+
+                    let dot_content =
+                        generate_dot_content(
+                            &component_map,
+                            None,
+                            &TreeOptions::default(),
+                            &node_attributes,
+                            &edge_attributes,
+                            &graph_style,
+                        );
+
+                    let output_path = output_dir.join(
+                        format!("{}.{}", component[0], image_format.extension()),
+                    );
+
+                    render_graph(
+                        |writer| writer.write_all(dot_content.as_bytes()),
+                        image_format,
+                        &output_path,
+                        &dot_bin,
+                        timeout,
+                    )?;
+                }
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            if let Some(segments) = split_by_prefix {
+                let output_dir = PathBuf::from(output_dir.unwrap_or(".".to_string()));
+                fs::create_dir_all(&output_dir)
+                    .with_context(|| format!("could not create {}", output_dir.display()))?;
+
+                let partitions = partition_by_prefix(&imports_map, segments);
+
+                info!("Found {} package groups", partitions.len());
+
+                for (partition_prefix, partition_map) in partitions {
+                    let mut partition_attributes = node_attributes.clone();
+
+                    for entry in partition_map.iter() {
+                        for import_value in entry.value() {
+                            if package_prefix(import_value, segments) != partition_prefix {
+                                partition_attributes
+                                    .entry(import_value.clone())
+                                    .or_insert_with(|| vec!["shape=folder".to_string()]);
+                            }
+                        }
+                    }
+
+                    let dot_content =
+                        generate_dot_content(
+                            &partition_map,
+                            None,
+                            &TreeOptions::default(),
+                            &partition_attributes,
+                            &edge_attributes,
+                            &graph_style,
+                        );
+
+                    let output_path = output_dir.join(
+                        format!("{}.{}", partition_prefix, image_format.extension()),
+                    );
+
+                    render_graph(
+                        |writer| writer.write_all(dot_content.as_bytes()),
+                        image_format,
+                        &output_path,
+                        &dot_bin,
+                        timeout,
+                    )?;
+                }
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            let (graph_map, graph_root_prefix, tree_options, edge_attributes) =
+                if let Some(ref dominator_root) = dominators {
+                    let Some(dominator_tree) =
+                        build_dominator_tree(&imports_map, dominator_root) else {
+                        bail!("package \"{}\" was not found", dominator_root);
+                    };
+
+                    let dominated = count_dominated(&dominator_tree, dominator_root);
+                    let reachable = dominator_tree.len();
+
+                    println!(
+                        "{} of {} packages are reachable from {}",
+                        reachable,
+                        imports_map.len(),
+                        dominator_root,
+                    );
+
+                    let mut chokepoints: Vec<(&String, &usize)> = dominated
+                        .iter()
+                        .filter(|(package_name, &count)| {
+                            *package_name != dominator_root && count > 0
+                        })
+                        .collect();
+                    chokepoints.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+                    for (package_name, &count) in chokepoints {
+                        println!("  {} dominates {} packages", package_name, count);
+
+                        if count * 100 >= reachable * CHOKEPOINT_PERCENT {
+                            node_attributes
+                                .entry(package_name.clone())
+                                .or_default()
+                                .extend([
+                                    "fillcolor=orangered".to_string(),
+                                    format!("tooltip=\"dominates {} packages\"", count),
+                                ]);
+                        }
+                    }
+
+                    let tree_options = TreeOptions {
+                        internal_only,
+                        target_prefix,
+                        exclude_packages: exclude_packages.clone(),
+                        bridge,
+                        ..TreeOptions::default()
+                    };
+
+                    if let Some(max_nodes) = max_nodes {
+                        check_max_nodes(&dominator_tree, None, &tree_options, max_nodes)?;
+                    }
+
+                    (dominator_tree, None, tree_options, HashMap::new())
+                } else {
+                    let tree_options = TreeOptions {
+                        depth,
+                        node_budget,
+                        internal_only,
+                        target_prefix,
+                        exclude_packages,
+                        bridge,
+                    };
+
+                    if let Some(max_nodes) = max_nodes {
+                        check_max_nodes(&imports_map, root_class_prefix.as_deref(), &tree_options, max_nodes)?;
+                    }
+
+                    (imports_map, root_class_prefix.as_deref(), tree_options, edge_attributes)
+                };
+
+            if graph_format != GraphFormat::Dot && graph_format != GraphFormat::Html {
+                let graph_content =
+                    generate_graph_content(
+                        graph_format,
+                        &graph_map,
+                        graph_root_prefix,
+                        &tree_options,
+                        &node_attributes,
+                        &edge_attributes,
+                        &graph_style,
+                    );
+
+                write_text_output(
+                    &graph_content,
+                    (!print_graph).then_some(svg_file_path.as_path()),
+                    text_encoding,
+                )?;
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            // the dot source is streamed to each destination rather than kept in memory
+            let write_dot = |writer: &mut dyn Write| {
+                write_dot_content(
+                    writer,
+                    &graph_map,
+                    graph_root_prefix,
+                    &tree_options,
+                    &node_attributes,
+                    &edge_attributes,
+                    &graph_style,
+                )
+            };
+
+            if let Some(ref dot_out) = dot_out {
+                stream_to_file(Path::new(dot_out), write_dot)?;
+            }
+
+            if render_image {
+                // an image rendered from the same dot source in the same format is reused
+                let mut hashing_writer = HashingWriter::new();
+                writeln!(hashing_writer, "{}", output_extension)?;
+                write_dot(&mut hashing_writer)?;
+
+                let dot_hash = format!("{:016x}", hashing_writer.hash);
+                let hash_path = render_hash_path(&svg_file_path);
+
+                let up_to_date = !force
+                    && svg_file_path.is_file()
+                    && fs::read_to_string(&hash_path).is_ok_and(|rendered_hash| rendered_hash.trim() == dot_hash);
+
+                if up_to_date {
+                    info!("{} is up to date", svg_file_path.display());
+                } else {
+                    if graph_format == GraphFormat::Html {
+                        info!("Generating html file...");
+
+                        let svg_content = run_graphviz(write_dot, ImageFormat::Svg, &dot_bin, timeout)?;
+                        let html_content =
+                            generate_html_content(
+                                &graph_map,
+                                graph_root_prefix,
+                                &tree_options,
+                                &String::from_utf8_lossy(&svg_content),
+                            )?;
+
+                        fs::write(&svg_file_path, html_content)
+                            .with_context(|| format!("could not write {}", svg_file_path.display()))?;
+                    } else {
+                        render_graph(write_dot, image_format, &svg_file_path, &dot_bin, timeout)?;
+                    }
+
+                    fs::write(&hash_path, dot_hash)
+                        .with_context(|| format!("could not write {}", hash_path.display()))?;
+                }
+            }
+        }
+        Commands::Json {
+            path,
+            out,
+            class_prefix,
+            depth,
+            output_encoding,
+            line_ending,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            let dependency_tree =
+                build_dependency_tree(
+                    &imports_map,
+                    class_prefix.as_deref(),
+                    &TreeOptions {
+                        depth,
+                        ..TreeOptions::default()
+                    },
+                );
+
+            let packages = export_package_imports(&imports_map, dependency_tree);
+
+            let json_content = serde_json::to_string_pretty(&packages)? + "\n";
+
+            write_text_output(
+                &json_content,
+                out.as_deref().map(Path::new),
+                TextEncoding::parse(output_encoding, line_ending)?,
+            )?;
+        }
+        Commands::Cycles {
+            path,
+            class_prefix,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let package_map = internal_imports_map(&imports_map, class_prefix.as_deref());
+
+            let cycles: Vec<Vec<String>> =
+                find_strongly_connected_components(&package_map)
+                    .into_iter()
+                    .filter(|component| component.len() > 1)
+                    .map(|component| find_cycle(&package_map, &component))
+                    .collect();
+
+            println!("Found {} dependency cycles", cycles.len());
+
+            for cycle in &cycles {
+                println!("  {}", cycle.join(" -> "));
+            }
+
+            if !cycles.is_empty() {
+                return Ok(ExitCode::from(2));
+            }
+        }
+        Commands::Toposort {
+            path,
+            class_prefix,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let package_map = internal_imports_map(&imports_map, class_prefix.as_deref());
+
+            match topological_order(&package_map) {
+                Ok(order) => {
+                    for package_name in order {
+                        println!("{}", package_name);
+                    }
+                }
+                Err(cycle) => {
+                    eprintln!("No build order exists because of the cycle {}", cycle.join(" -> "));
+                    return Ok(ExitCode::from(2));
+                }
+            }
+        }
+        Commands::Roots {
+            path,
+            class_prefix,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let (roots, _) = find_roots_and_leaves(&imports_map, class_prefix.as_deref());
+
+            for package_name in roots {
+                println!("{}", package_name);
+            }
+        }
+        Commands::Leaves {
+            path,
+            class_prefix,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let (_, leaves) = find_roots_and_leaves(&imports_map, class_prefix.as_deref());
+
+            for package_name in leaves {
+                println!("{}", package_name);
+            }
+        }
+        Commands::Orphans {
+            path,
+            class_prefix,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let orphans = find_orphans(&imports_map, class_prefix.as_deref());
+
+            for (package_name, external_imports) in &orphans {
+                if *external_imports == 0 {
+                    println!("{} (isolated)", package_name);
+                } else {
+                    println!("{} (only external dependencies: {})", package_name, external_imports);
+                }
+            }
+
+            info!("Found {} orphan packages", orphans.len());
+        }
+        Commands::LongestPath {
+            path,
+            class_prefix,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let package_map = internal_imports_map(&imports_map, class_prefix.as_deref());
+            let chain = find_longest_chain(&package_map);
+
+            let steps: Vec<String> = chain
+                .iter()
+                .map(|component| match component.as_slice() {
+                    [package_name] => package_name.clone(),
+                    _ => format!("{{{}}}", component.join(", ")),
+                })
+                .collect();
+
+            println!("{}", steps.join(" -> "));
+            println!("Length: {} dependencies", chain.len().saturating_sub(1));
+        }
+        Commands::Path {
+            path,
+            from,
+            to,
+            graph_out,
+            dot_bin,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let package_map = internal_imports_map(&imports_map, None);
+
+            for package_name in [&from, &to] {
+                if !package_map.contains_key(package_name) {
+                    bail!("package \"{}\" was not found", package_name);
+                }
+            }
+
+            let Some(dependency_path) = find_shortest_path(&package_map, &from, &to) else {
+                println!("No dependency path from {} to {}", from, to);
+                return Ok(ExitCode::from(2));
+            };
+
+            println!("{}", dependency_path.join(" -> "));
+
+            if let Some(graph_out) = graph_out {
+                let graph_out = PathBuf::from(graph_out);
+                warn_extension_mismatch(&graph_out, ImageFormat::Svg.extension());
+
+                let path_edges: HashSet<(&String, &String)> = dependency_path
+                    .iter()
+                    .zip(dependency_path.iter().skip(1))
+                    .collect();
+
+                // the chain's edges stand out in red while every other edge is dimmed
+                let mut edge_attributes = HashMap::<(String, String), Vec<String>>::new();
+
+                for entry in package_map.iter() {
+                    for import_value in entry.value() {
+                        let attributes =
+                            if path_edges.contains(&(entry.key(), import_value)) {
+                                vec!["color=red".to_string(), "penwidth=2.5".to_string()]
+                            } else {
+                                vec!["color=gray30".to_string()]
+                            };
+
+                        edge_attributes.insert((entry.key().to_string(), import_value.to_string()), attributes);
+                    }
+                }
+
+                let dot_content =
+                    generate_dot_content(
+                        &package_map,
+                        None,
+                        &TreeOptions::default(),
+                        &HashMap::new(),
+                        &edge_attributes,
+                        &GraphStyle::default(),
+                    );
+
+                render_graph(
+                    |writer| writer.write_all(dot_content.as_bytes()),
+                    ImageFormat::Svg,
+                    &graph_out,
+                    &dot_bin,
+                    None,
+                )?;
+            }
+        }
+        Commands::Closure {
+            path,
+            from,
+            depth,
+            graph_out,
+            dot_bin,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let package_map = internal_imports_map(&imports_map, None);
+
+            if !package_map.contains_key(&from) {
+                bail!("package \"{}\" was not found", from);
+            }
+
+            let closure = find_closure(&package_map, &from, depth);
+
+            let mut reachable: Vec<String> = closure
+                .iter()
+                .map(|entry| entry.key().to_string())
+                .filter(|package_name| *package_name != from)
+                .collect();
+            reachable.sort();
+
+            for package_name in &reachable {
+                println!("{}", package_name);
+            }
+
+            info!("{} depends on {} packages", from, reachable.len());
+
+            if let Some(graph_out) = graph_out {
+                let graph_out = PathBuf::from(graph_out);
+                warn_extension_mismatch(&graph_out, ImageFormat::Svg.extension());
+
+                let dot_content =
+                    generate_dot_content(
+                        &closure,
+                        None,
+                        &TreeOptions::default(),
+                        &HashMap::new(),
+                        &HashMap::new(),
+                        &GraphStyle::default(),
+                    );
+
+                render_graph(
+                    |writer| writer.write_all(dot_content.as_bytes()),
+                    ImageFormat::Svg,
+                    &graph_out,
+                    &dot_bin,
+                    None,
+                )?;
+            }
+        }
+        Commands::Diff {
+            old,
+            new,
+            graph_out,
+            dot_bin,
+        } => {
+            let old_imports_map = load_graph_input(&old, parse_options)?;
+            let new_imports_map = load_graph_input(&new, parse_options)?;
+
+            info!("Found {} old and {} new packages", old_imports_map.len(), new_imports_map.len());
+
+            let (added_edges, removed_edges) = diff_edges(&old_imports_map, &new_imports_map);
+            let new_cycles = find_new_cycles(&old_imports_map, &new_imports_map);
+
+            for (package_name, import_value) in &added_edges {
+                println!("+ {} -> {}", package_name, import_value);
+            }
+
+            for (package_name, import_value) in &removed_edges {
+                println!("- {} -> {}", package_name, import_value);
+            }
+
+            for cycle in &new_cycles {
+                println!("New cycle: {}", cycle.join(" -> "));
+            }
+
+            info!(
+                "{} edges added, {} removed, {} new cycles",
+                added_edges.len(),
+                removed_edges.len(),
+                new_cycles.len(),
+            );
+
+            if let Some(graph_out) = graph_out {
+                let graph_out = PathBuf::from(graph_out);
+                warn_extension_mismatch(&graph_out, ImageFormat::Svg.extension());
+
+                let combined_map = new_imports_map.clone();
+                let mut edge_attributes = HashMap::<(String, String), Vec<String>>::new();
+
+                for edge in &added_edges {
+                    edge_attributes.insert(edge.clone(), vec!["color=green".to_string(), "penwidth=2".to_string()]);
+                }
+
+                for (package_name, import_value) in &removed_edges {
+                    combined_map.entry(package_name.clone()).or_default().push(import_value.clone());
+
+                    edge_attributes.insert(
+                        (package_name.clone(), import_value.clone()),
+                        vec!["color=red".to_string(), "style=dashed".to_string()],
+                    );
+                }
+
+                let dot_content =
+                    generate_dot_content(
+                        &combined_map,
+                        None,
+                        &TreeOptions::default(),
+                        &HashMap::new(),
+                        &edge_attributes,
+                        &GraphStyle::default(),
+                    );
+
+                render_graph(
+                    |writer| writer.write_all(dot_content.as_bytes()),
+                    ImageFormat::Svg,
+                    &graph_out,
+                    &dot_bin,
+                    None,
+                )?;
+            }
+
+            if !new_cycles.is_empty() {
+                return Ok(ExitCode::from(2));
+            }
+        }
+        Commands::Similar {
+            path,
+            threshold,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            for (package_name, other_name, similarity) in
+                find_similar_packages(&imports_map, threshold) {
+                println!("{:.2}  {}  {}", similarity, package_name, other_name);
+            }
+        }
+        Commands::Hotspots {
+            path,
+            min_in,
+            min_out,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            for hotspot in find_hotspots(&imports_map, min_in, min_out) {
+                println!(
+                    "{}  in={} out={} score={}",
+                    hotspot.package_name,
+                    hotspot.dependents.len(),
+                    hotspot.dependencies.len(),
+                    hotspot.score(),
+                );
+
+                let top = |packages: &[String]| {
+                    packages[..packages.len().min(HOTSPOT_NEIGHBOURS)].join(", ")
+                };
+
+                println!("  depends on: {}", top(&hotspot.dependencies));
+                println!("  used by: {}", top(&hotspot.dependents));
+            }
+        }
+        Commands::Externals {
+            path,
+            segments,
+        } => {
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let (internal_count, tally) = tally_external_imports(&imports_map, segments);
+            let external_count: usize = tally.iter().map(|(_, count)| count).sum();
+
+            println!("{} internal imports, {} external imports", internal_count, external_count);
+
+            for (namespace, count) in tally {
+                println!("  {}.*: {}", namespace, count);
+            }
+        }
+        Commands::Metrics {
+            path,
+            sort_by,
+            advise,
+            max_fan_in,
+            max_fan_out,
+        } => {
+            let Ok(sort_key) =
+                MetricsSortKey::from_str(
+                    sort_by.unwrap_or("fan-in".to_string()).as_str(),
+                ) else {
+                bail!("unsupported sort column; expected fan-in, fan-out or instability");
+            };
+
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let mut metrics = compute_package_metrics(&imports_map);
+
+            if advise {
+                let max_fan_in = max_fan_in.unwrap_or(10);
+                let max_fan_out = max_fan_out.unwrap_or(20);
+
+                let advised = find_advised_packages(&metrics, max_fan_in, max_fan_out);
+
+                for package_metrics in &advised {
+                    let suggestion =
+                        if package_metrics.fan_in > max_fan_in && package_metrics.fan_out > max_fan_out {
+                            "hub both depending on and depended upon by much; consider splitting it"
+                        } else if package_metrics.fan_in > max_fan_in {
+                            "widely imported; keep it stable or move its shared parts behind interfaces"
+                        } else {
+                            "depends on much; consider moving some responsibilities out"
+                        };
+
+                    println!(
+                        "{}: fan-in {} (max {}), fan-out {} (max {}): {}",
+                        package_metrics.package_name,
+                        package_metrics.fan_in,
+                        max_fan_in,
+                        package_metrics.fan_out,
+                        max_fan_out,
+                        suggestion,
+                    );
+                }
+
+                info!("Flagged {} of {} packages", advised.len(), metrics.len());
+
+                return Ok(ExitCode::SUCCESS);
+            }
+
+            metrics.sort_by(|a, b| {
+                match sort_key {
+                    MetricsSortKey::FanIn => b.fan_in.cmp(&a.fan_in),
+                    MetricsSortKey::FanOut => b.fan_out.cmp(&a.fan_out),
+                    MetricsSortKey::Instability => b.instability().total_cmp(&a.instability()),
+                }
+                .then(a.package_name.cmp(&b.package_name))
+            });
+
+            let width = metrics
+                .iter()
+                .map(|package_metrics| package_metrics.package_name.len())
+                .max()
+                .unwrap_or(0)
+                .max("package".len());
+
+            println!("{:<width$}  fan-in  fan-out  instability", "package");
+
+            for package_metrics in &metrics {
+                println!(
+                    "{:<width$}  {:<6}  {:<7}  {:.2}",
+                    package_metrics.package_name,
+                    package_metrics.fan_in,
+                    package_metrics.fan_out,
+                    package_metrics.instability(),
+                );
+            }
+        }
+        Commands::Wildcards {
+            path,
+            strict,
+            exclude,
+            gitignore,
+            follow_symlinks,
+            lang,
+        } => {
+            let walk_filter = source_walk_filter(&exclude, gitignore, follow_symlinks, &lang, parse_options)?;
+            let mut wildcard_count = 0;
+
+            for file_path in collect_source_files(input_path(&path)?, &walk_filter) {
+                for (line_number, import_value) in find_wildcard_imports(&file_path, parse_options) {
+                    println!("{}:{}: import {}", file_path.display(), line_number, import_value);
+                    wildcard_count += 1;
+                }
+            }
+
+            println!("Found {} wildcard imports", wildcard_count);
+
+            if strict && wildcard_count > 0 {
+                return Ok(ExitCode::from(2));
+            }
+        }
+        Commands::CheckRules {
+            path,
+            rules,
+        } => {
+            let rules_content = fs::read_to_string(&rules)
+                .with_context(|| format!("could not read rules file {}", rules))?;
+            let dependency_rules = parse_dependency_rules(&rules_content)
+                .with_context(|| format!("invalid rules file {}", rules))?;
+
+            let imports_map =
+                scan_input(&path, parse_options)?;
+
+            info!("Found {} packages", imports_map.len());
+
+            let violations = find_rule_violations(&imports_map, &dependency_rules);
+
+            for (package_name, import_value, rule) in &violations {
+                println!(
+                    "{} -> {} breaks rule {} -> {}",
+                    package_name,
+                    import_value,
+                    rule.source_prefix,
+                    rule.target_prefix,
+                );
+            }
+
+            if !violations.is_empty() {
+                eprintln!("Found {} rule violations", violations.len());
+                return Ok(ExitCode::from(2));
+            }
+        }
+        Commands::Lint {
+            path,
+            source_root,
+            exclude,
+            gitignore,
+            follow_symlinks,
+            lang,
+        } => {
+            let walk_filter = source_walk_filter(&exclude, gitignore, follow_symlinks, &lang, parse_options)?;
+            let folder_path = input_path(&path)?;
+            let source_root =
+                match source_root {
+                    Some(ref source_root) => input_path(source_root)?,
+                    None => folder_path,
+                };
+
+            let mismatches = find_package_mismatches(folder_path, source_root, &walk_filter);
+
+            for (file_path, declared_package, implied_package) in &mismatches {
+                println!(
+                    "{}: declared {} but directory implies {}",
+                    file_path.display(),
+                    declared_package,
+                    implied_package,
+                );
+            }
+
+            if !mismatches.is_empty() {
+                eprintln!("Found {} package mismatches", mismatches.len());
+                return Ok(ExitCode::from(2));
+            }
+        }
+        Commands::Watch {
+            path,
+            debounce,
+            mut graph_options,
+        } => {
+            let folder_path = input_path(&path)?;
+
+            let has_cache = graph_options
+                .iter()
+                .any(|option| option == "--cache" || option.starts_with("--cache="));
+
+            if !has_cache {
+                graph_options.push(format!("--cache={}", watch_cache_path(folder_path).display()));
+            }
+
+            // bad options are reported before anything is watched
+            watch_graph_command(&path, &graph_options)?;
+
+            watch_folder(
+                folder_path,
+                &path,
+                Duration::from_millis(debounce.unwrap_or(300)),
+                &graph_options,
+                parse_options,
+            )?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::test_support::fake_dot;
+    use crate::test_support::{fixture, git};
+
+    // Function to run a jadep command line, without the program name
+    fn run_jadep(
+        args: &[&str],
+    ) -> anyhow::Result<ExitCode> {
+        let cli = Cli::parse_from(std::iter::once("jadep").chain(args.iter().copied()));
+
+        run(cli.command, &ParseOptions::default())
+    }
+
+    #[test]
+    fn graphs_over_max_nodes_are_rejected_before_graphviz_runs() {
+        let folder = fixture("max-nodes", &[
+            ("com/a/A.java", "package com.a;\nimport com.b.B;\nimport com.c.C;\n"),
+            ("com/b/B.java", "package com.b;\n"),
+            ("com/c/C.java", "package com.c;\n"),
+        ]);
+        let graph_out = folder.join("graph.svg");
+
+        let error = run_jadep(&[
+            "graph",
+            "-p", folder.to_str().unwrap(),
+            "-g", graph_out.to_str().unwrap(),
+            "--max-nodes", "2",
+            "--dot-bin", "/nonexistent/dot",
+        ])
+        .unwrap_err();
+
+        assert!(error.to_string().contains("the graph has 5 nodes, more than the maximum of 2"));
+        assert!(!graph_out.exists());
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn missing_paths_fail_and_found_cycles_exit_with_two() {
+        let missing = std::env::temp_dir().join(format!("jadep-cli-missing-{}", std::process::id()));
+
+        assert!(run_jadep(&["cycles", "-p", missing.to_str().unwrap()]).is_err());
+
+        let folder = fixture("cycles", &[
+            ("com/a/A.java", "package com.a;\nimport com.b.B;\n"),
+            ("com/b/B.java", "package com.b;\nimport com.a.A;\n"),
+        ]);
+
+        let exit_code = run_jadep(&["cycles", "-p", folder.to_str().unwrap()]).unwrap();
+
+        fs::remove_dir_all(folder).unwrap();
+
+        assert_eq!(exit_code, ExitCode::from(2));
+    }
+
+    #[test]
+    fn config_defaults_apply_unless_overridden_by_flags() {
+        let folder = fixture("config", &[
+            ("com/a/A.java", "package com.a;\nimport com.b.B;\n"),
+            ("jadep.toml", "rank_dir = \"tb\"\n"),
+        ]);
+        let config_path = folder.join("jadep.toml");
+        let dot_out = folder.join("graph.dot");
+
+        let generate_dot = |extra_args: &[&str]| {
+            let mut args = vec![
+                "graph",
+                "-p", folder.to_str().unwrap(),
+                "--config", config_path.to_str().unwrap(),
+                "--dot-out", dot_out.to_str().unwrap(),
+            ];
+            args.extend_from_slice(extra_args);
+            run_jadep(&args).unwrap();
+            fs::read_to_string(&dot_out).unwrap()
+        };
+
+        let from_config = generate_dot(&[]);
+        let from_flag = generate_dot(&["--rank-dir", "bt"]);
+
+        fs::remove_dir_all(folder).unwrap();
+
+        assert!(from_config.contains("rankdir=TB;"));
+        assert!(from_flag.contains("rankdir=BT;"));
+    }
+
+    #[test]
+    fn strict_fails_on_a_double_package_that_otherwise_only_warns() {
+        let folder = fixture("strict", &[
+            ("com/a/A.java", "package com.a;\npackage com.b;\nimport com.c.C;\n"),
+        ]);
+        let dot_out = folder.join("graph.dot");
+        let graph_args = [
+            "graph",
+            "-p", folder.to_str().unwrap(),
+            "--dot-out", dot_out.to_str().unwrap(),
+        ];
+
+        let cache_path = folder.join("cache.json");
+        let cache_args = ["--cache", cache_path.to_str().unwrap()];
+
+        let default_run = run_jadep(&[&graph_args[..], &cache_args].concat());
+        let strict_run = run_jadep(&[&graph_args[..], &["--strict"]].concat());
+        // the file is taken from the cache this time, along with its problem
+        let cached_strict_run = run_jadep(&[&graph_args[..], &cache_args, &["--strict"]].concat());
+
+        fs::remove_dir_all(folder).unwrap();
+
+        assert_eq!(default_run.unwrap(), ExitCode::SUCCESS);
+        assert!(strict_run.unwrap_err().to_string().starts_with("found 1 parse problems"));
+        assert!(cached_strict_run.unwrap_err().to_string().starts_with("found 1 parse problems"));
+    }
+
+    #[test]
+    fn dry_run_summarizes_the_graph_without_rendering_it() {
+        let folder = fixture("dry-run", &[
+            ("com/a/A.java", "package com.a;\nimport com.b.B;\nimport java.util.List;\n"),
+            ("com/b/B.java", "package com.b;\nimport com.c.C;\n"),
+            ("com/c/C.java", "package com.c;\n"),
+        ]);
+        let graph_out = folder.join("graph.svg");
+
+        let exit_code = run_jadep(&[
+            "graph",
+            "-p", folder.to_str().unwrap(),
+            "-g", graph_out.to_str().unwrap(),
+            "--dry-run",
+            "--dot-bin", "/nonexistent/dot",
+        ])
+        .unwrap();
+        let imports_map = scan_input(folder.to_str().unwrap(), &ParseOptions::default()).unwrap();
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert!(!graph_out.exists());
+        assert_eq!(
+            dry_run_summary(&imports_map, Some("com.a"), &TreeOptions { depth: Some(1), ..TreeOptions::default() }),
+            "Packages: 3\nEdges: 3\nGraph nodes: 3\nGraph edges: 2\nRoots (1):\n  com.a\n",
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unchanged_graphs_are_not_rendered_again() {
+        let folder = fixture("render-cache", &[
+            ("src/com/a/A.java", "package com.a;\nimport com.b.B;\n"),
+        ]);
+        let runs_path = folder.join("runs.txt");
+        let dot_bin = fake_dot(&folder, &format!("echo run >> '{}'\ncat > /dev/null\necho '<svg/>'", runs_path.display()));
+        let graph_out = folder.join("graph.svg");
+
+        for _ in 0..2 {
+            run_jadep(&[
+                "graph",
+                "-p", folder.join("src").to_str().unwrap(),
+                "-g", graph_out.to_str().unwrap(),
+                "--dot-bin", dot_bin.to_str().unwrap(),
+            ])
+            .unwrap();
+        }
+
+        let runs = fs::read_to_string(&runs_path).unwrap();
+        let svg_content = fs::read_to_string(&graph_out).unwrap();
+
+        fs::remove_dir_all(folder).unwrap();
+
+        assert_eq!(runs.lines().count(), 1);
+        assert_eq!(svg_content, "<svg/>\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn split_graphs_are_written_one_per_prefix() {
+        let folder = fixture("split", &[
+            ("src/com/a/x/X.java", "package com.a.x;\nimport com.a.y.Y;\nimport com.b.z.Z;\n"),
+            ("src/com/a/y/Y.java", "package com.a.y;\n"),
+            ("src/com/b/z/Z.java", "package com.b.z;\n"),
+        ]);
+        let dot_bin = fake_dot(&folder, "cat");
+        let output_dir = folder.join("graphs");
+
+        run_jadep(&[
+            "graph",
+            "-p", folder.join("src").to_str().unwrap(),
+            "--split-by-prefix", "2",
+            "--output-dir", output_dir.to_str().unwrap(),
+            "--dot-bin", dot_bin.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        let mut graph_files: Vec<String> = fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        graph_files.sort();
+        let com_a = fs::read_to_string(output_dir.join("com.a.svg")).unwrap();
+
+        fs::remove_dir_all(folder).unwrap();
+
+        assert_eq!(graph_files, vec!["com.a.svg", "com.b.svg"]);
+        assert!(com_a.contains("\"com.a.x\" -> \"com.a.y.Y\""));
+        assert!(com_a.contains("\"com.a.x\" -> \"com.b\""));
+    }
+
+    #[test]
+    fn custom_dot_attributes_are_validated_and_written() {
+        assert_eq!(
+            parse_dot_attribute("--graph-attr", "ratio=compress").unwrap(),
+            ("ratio".to_string(), "compress".to_string()),
+        );
+        assert!(parse_dot_attribute("--node-attr", "shape").is_err());
+        assert!(parse_dot_attribute("--edge-attr", "1color=red").is_err());
+
+        let folder = fixture("dot-attributes", &[("com/a/A.java", "package com.a;\nimport com.b.B;\n")]);
+        let dot_out = folder.join("graph.dot");
+
+        run_jadep(&[
+            "graph",
+            "-p", folder.to_str().unwrap(),
+            "--dot-out", dot_out.to_str().unwrap(),
+            "--graph-attr", "ratio=compress",
+            "--edge-attr", "arrowhead=vee",
+        ])
+        .unwrap();
+
+        let dot_content = fs::read_to_string(&dot_out).unwrap();
+
+        fs::remove_dir_all(folder).unwrap();
+
+        assert!(dot_content.contains("  graph [ratio=\"compress\"];\n"));
+        assert!(dot_content.contains("  edge [arrowhead=\"vee\"];\n"));
+    }
+
+    #[test]
+    fn rank_directions_parse_in_any_case_and_reject_unknown_ones() {
+        assert_eq!(parse_rank_dir("TB"), Ok(RankDir::TB));
+        assert_eq!(parse_rank_dir("Lr"), Ok(RankDir::LR));
+
+        let Commands::Graph { rank_dir, .. } = Cli::parse_from(["jadep", "graph", "-p", ".", "--rank-dir", "BT"]).command else {
+            panic!("expected a graph command");
+        };
+        assert_eq!(rank_dir, Some(RankDir::BT));
+
+        let error = Cli::try_parse_from(["jadep", "graph", "-p", ".", "--rank-dir", "diagonal"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("unsupported rank direction \"diagonal\"; expected tb, bt, lr or rl"), "{}", error);
+    }
+
+    #[test]
+    fn churn_is_counted_on_the_renamed_nodes() {
+        let folder = fixture("churn", &[
+            ("com/acme/a/A.java", "package com.acme.a;\n"),
+            ("com/acme/b/B.java", "package com.acme.b;\nimport com.acme.a.A;\n"),
+        ]);
+        let dot_out = folder.join("graph.dot");
+
+        git(&folder, &["init", "-q"]);
+        git(&folder, &["add", "."]);
+        git(&folder, &["commit", "-qm", "add the sources"]);
+
+        run_jadep(&[
+            "graph",
+            "-p", folder.to_str().unwrap(),
+            "--dot-out", dot_out.to_str().unwrap(),
+            "--churn-since", "1970-01-01",
+            "--collapse-depth", "2",
+        ])
+        .unwrap();
+
+        let dot_content = fs::read_to_string(&dot_out).unwrap();
+
+        fs::remove_dir_all(folder).unwrap();
+
+        // the commit touched both packages, which are drawn as a single node
+        assert!(dot_content.contains("tooltip=\"1 commits\""), "{}", dot_content);
+        assert!(!dot_content.contains("\"com.acme.a\""), "{}", dot_content);
+    }
+
+    #[test]
+    fn dot_out_writes_the_dot_source_without_running_graphviz() {
+        let folder = fixture("dot-out", &[("com/a/A.java", "package com.a;\nimport com.b.B;\n")]);
+        let dot_out = folder.join("graph.dot");
+        let graph_out = folder.join("graph.svg");
+        let dot_args = [
+            "graph",
+            "-p", folder.to_str().unwrap(),
+            "--dot-out", dot_out.to_str().unwrap(),
+            "--dot-bin", "/nonexistent/dot",
+        ];
+
+        let dot_only_run = run_jadep(&dot_args);
+        let dot_only_content = fs::read_to_string(&dot_out);
+        fs::remove_file(&dot_out).unwrap();
+
+        // an image is asked for as well, so the missing graphviz is only noticed after the dot file is out
+        let image_run = run_jadep(&[&dot_args[..], &["-g", graph_out.to_str().unwrap()]].concat());
+        let image_run_content = fs::read_to_string(&dot_out);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(dot_only_run.unwrap(), ExitCode::SUCCESS);
+        assert!(dot_only_content.unwrap().contains("\"com.a\" -> \"com.b.B\";"));
+        assert!(image_run.unwrap_err().to_string().starts_with("graphviz `/nonexistent/dot` not found"));
+        assert!(image_run_content.unwrap().contains("\"com.a\" -> \"com.b.B\";"));
+        assert!(!graph_out.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cyclic_components_are_rendered_one_graph_each() {
+        // the imports name the packages themselves, so they close the cycles
+        let folder = fixture("split-scc", &[
+            ("src/com/a/A.java", "package com.a;\nimport com.b;\n"),
+            ("src/com/b/B.java", "package com.b;\nimport com.a;\nimport com.c;\n"),
+            ("src/com/c/C.java", "package com.c;\nimport com.d;\n"),
+            ("src/com/d/D.java", "package com.d;\nimport com.c;\n"),
+            ("src/com/e/E.java", "package com.e;\nimport com.a;\n"),
+        ]);
+        let dot_bin = fake_dot(&folder, "cat");
+        let output_dir = folder.join("graphs");
+
+        run_jadep(&[
+            "graph",
+            "-p", folder.join("src").to_str().unwrap(),
+            "--split-by-scc",
+            "--output-dir", output_dir.to_str().unwrap(),
+            "--dot-bin", dot_bin.to_str().unwrap(),
+        ])
+        .unwrap();
+
+        let mut graph_files: Vec<String> = fs::read_dir(&output_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        graph_files.sort();
+        let com_a = fs::read_to_string(output_dir.join("com.a.svg")).unwrap();
+
+        fs::remove_dir_all(folder).unwrap();
+
+        // edges leaving a component, like the one to com.c, are left out of its graph
+        assert_eq!(graph_files, vec!["com.a.svg", "com.c.svg"]);
+        assert!(com_a.contains("\"com.a\" -> \"com.b\";"), "{}", com_a);
+        assert!(com_a.contains("\"com.b\" -> \"com.a\";"), "{}", com_a);
+        assert!(!com_a.contains("com.c"), "{}", com_a);
+        assert!(!com_a.contains("com.e"), "{}", com_a);
+    }
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+// Function to apply the byte order mark and line ending choices to a text export
+fn encode_text_output(
+    content: &str,
+    output_encoding: OutputEncoding,
+    line_ending: LineEnding,
+) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(content.len() + 3);
+
+    if let OutputEncoding::Utf8Bom = output_encoding {
+        encoded.extend_from_slice("\u{feff}".as_bytes());
+    }
+
+    match line_ending {
+        LineEnding::Lf => encoded.extend_from_slice(content.as_bytes()),
+        LineEnding::Crlf => {
+            for line in content.split_inclusive('\n') {
+                match line.strip_suffix('\n') {
+                    Some(line) => {
+                        encoded.extend_from_slice(line.strip_suffix('\r').unwrap_or(line).as_bytes());
+                        encoded.extend_from_slice(b"\r\n");
+                    }
+                    None => encoded.extend_from_slice(line.as_bytes()),
+                }
+            }
+        }
+    }
+
+    encoded
+}
+
+// Byte order mark and line ending requested for a text export
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TextEncoding {
+    output_encoding: OutputEncoding,
+    line_ending: LineEnding,
+}
+
+impl TextEncoding {
+    // Function to parse the --output-encoding and --line-ending options
+    pub(crate) fn parse(
+        output_encoding: Option<String>,
+        line_ending: Option<String>,
+    ) -> anyhow::Result<TextEncoding> {
+        let Ok(output_encoding) =
+            OutputEncoding::from_str(
+                output_encoding.unwrap_or("utf8".to_string()).as_str(),
+            ) else {
+            bail!("unsupported output encoding; expected utf8 or utf8-bom");
+        };
+
+        let Ok(line_ending) =
+            LineEnding::from_str(
+                line_ending.unwrap_or("lf".to_string()).as_str(),
+            ) else {
+            bail!("unsupported line ending; expected lf or crlf");
+        };
+
+        Ok(TextEncoding { output_encoding, line_ending })
+    }
+}
+
+// Function to write a text export to a file, or to stdout if no file is given,
+// with the requested byte order mark and line ending
+pub(crate) fn write_text_output(
+    content: &str,
+    out: Option<&Path>,
+    text_encoding: TextEncoding,
+) -> anyhow::Result<()> {
+    let encoded = encode_text_output(content, text_encoding.output_encoding, text_encoding.line_ending);
+
+    if let Some(out) = out {
+        fs::write(out, encoded).with_context(|| format!("could not write {}", out.display()))?;
+    } else {
+        std::io::stdout().write_all(&encoded).context("could not write to stdout")?;
+    }
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+enum OutputEncoding {
+    #[serde(rename = "utf8")]
+    Utf8,
+    #[serde(rename = "utf8-bom")]
+    Utf8Bom,
+}
+
+impl FromStr for OutputEncoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(OutputEncoding::Utf8),
+            "utf8-bom" => Ok(OutputEncoding::Utf8Bom),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+enum LineEnding {
+    #[serde(rename = "lf")]
+    Lf,
+    #[serde(rename = "crlf")]
+    Crlf,
+}
+
+impl FromStr for LineEnding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_output_gets_bom_and_crlf_line_endings() {
+        let text_encoding = TextEncoding::parse(Some("utf8-bom".to_string()), Some("crlf".to_string())).unwrap();
+
+        let encoded = encode_text_output("a,b\r\nc,d\ne", text_encoding.output_encoding, text_encoding.line_ending);
+
+        assert_eq!(encoded, "\u{feff}a,b\r\nc,d\r\ne".as_bytes());
+    }
+
+    #[test]
+    fn unknown_text_encodings_are_rejected() {
+        assert!(TextEncoding::parse(Some("latin1".to_string()), None).is_err());
+        assert!(TextEncoding::parse(None, Some("cr".to_string())).is_err());
+    }
+}
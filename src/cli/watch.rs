@@ -0,0 +1,167 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use log::{info, warn};
+
+use jadep::parse::{Language, ParseOptions};
+
+use super::{run, Cli, Commands, HashingWriter};
+
+// Function to format the UTC time of day as HH:MM:SS
+fn clock_time(
+    time: SystemTime,
+) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs()).unwrap_or(0) % 86400;
+
+    format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+// Function to check whether a file system event touches a source file
+fn is_source_change(
+    event: &notify::Event,
+) -> bool {
+    !event.kind.is_access() && event.paths.iter().any(|path| Language::of_file(path).is_some())
+}
+
+// Function to get the parse cache a folder is watched with unless --cache is given;
+// it is named after the folder, so watching the same folder again reuses it instead
+// of leaving another file behind in the temp dir
+pub(crate) fn watch_cache_path(
+    folder_path: &Path,
+) -> PathBuf {
+    let folder_path = folder_path.canonicalize().unwrap_or_else(|_| folder_path.to_path_buf());
+
+    let mut hashing_writer = HashingWriter::new();
+    let _ = write!(hashing_writer, "{}", folder_path.display());
+
+    std::env::temp_dir().join(format!("jadep-watch-{:016x}.json", hashing_writer.hash))
+}
+
+// Function to parse the graph command a watch regenerates with
+pub(crate) fn watch_graph_command(
+    path: &str,
+    graph_options: &[String],
+) -> anyhow::Result<Commands> {
+    let arguments = ["jadep", "graph", "--path", path]
+        .into_iter()
+        .map(str::to_string)
+        .chain(graph_options.iter().cloned());
+
+    match Cli::try_parse_from(arguments) {
+        Ok(cli) => Ok(cli.command),
+        Err(error) => bail!(
+            "invalid graph options: {}",
+            error.to_string().lines().next().unwrap_or_default().trim_start_matches("error: "),
+        ),
+    }
+}
+
+// Function to regenerate a graph once, then again after every debounced batch of
+// source file changes below the folder; failed runs are reported and watching goes on
+pub(crate) fn watch_folder(
+    folder_path: &Path,
+    path: &str,
+    debounce: Duration,
+    graph_options: &[String],
+    parse_options: &ParseOptions,
+) -> anyhow::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+
+    let mut watcher = notify::recommended_watcher(sender)
+        .context("could not start watching for file changes")?;
+
+    notify::Watcher::watch(&mut watcher, folder_path, notify::RecursiveMode::Recursive)
+        .with_context(|| format!("could not watch {}", folder_path.display()))?;
+
+    loop {
+        let started = Instant::now();
+
+        match run(watch_graph_command(path, graph_options)?, parse_options) {
+            Ok(_) => info!(
+                "[{}] Regenerated graph in {:.2?}",
+                clock_time(SystemTime::now()),
+                started.elapsed(),
+            ),
+            Err(error) => eprintln!("[{}] Error: {:#}", clock_time(SystemTime::now()), error),
+        }
+
+        info!("Watching {} for changes...", folder_path.display());
+
+        // wait for a source change, then until none came in for the debounce interval
+        loop {
+            match receiver.recv() {
+                Ok(Ok(event)) if is_source_change(&event) => break,
+                Ok(Ok(_)) => continue,
+                Ok(Err(error)) => warn!("{}", error),
+                Err(_) => bail!("stopped receiving file changes"),
+            }
+        }
+
+        while receiver.recv_timeout(debounce).is_ok() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::fixture;
+
+    #[test]
+    fn touching_a_source_file_triggers_a_regeneration() {
+        let folder = fixture("watch", &[]);
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).unwrap();
+        notify::Watcher::watch(&mut watcher, &folder, notify::RecursiveMode::Recursive).unwrap();
+
+        std::fs::write(folder.join("README.md"), "notes\n").unwrap();
+        std::fs::write(folder.join("App.java"), "package com.acme.app;\n").unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(Ok(event)) = receiver.recv_timeout(Duration::from_secs(5)) {
+            let source_change = is_source_change(&event);
+            events.push(event);
+
+            if source_change {
+                break;
+            }
+        }
+
+        drop(watcher);
+        std::fs::remove_dir_all(&folder).unwrap();
+
+        assert!(events.last().is_some_and(|event| event.paths.iter().any(|path| path.ends_with("App.java"))));
+
+        let access = notify::Event::new(notify::EventKind::Access(notify::event::AccessKind::Any))
+            .add_path(folder.join("App.java"));
+        assert!(!is_source_change(&access));
+    }
+
+    #[test]
+    fn graph_options_of_a_watch_are_checked_up_front() {
+        let Commands::Graph { path, depth, .. } = watch_graph_command("src", &["--depth".to_string(), "2".to_string()]).unwrap() else {
+            panic!("expected a graph command");
+        };
+
+        assert_eq!(path, vec!["src"]);
+        assert_eq!(depth, Some(2));
+        assert!(watch_graph_command("src", &["--no-such-option".to_string()]).is_err());
+    }
+
+    #[test]
+    fn watching_a_folder_again_reuses_its_cache() {
+        let folder = fixture("watch-cache", &[("a/A.java", "package a;\n")]);
+
+        let cache_path = watch_cache_path(&folder);
+
+        assert_eq!(watch_cache_path(&folder.join("a/..")), cache_path);
+        assert_ne!(watch_cache_path(&folder.join("a")), cache_path);
+        assert_eq!(cache_path.parent(), Some(std::env::temp_dir().as_path()));
+
+        std::fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -0,0 +1,357 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+// Function to stream generated content into a file through a buffer
+pub fn stream_to_file(
+    output_path: &Path,
+    write_content: impl FnOnce(&mut dyn Write) -> std::io::Result<()>,
+) -> anyhow::Result<()> {
+    let output_file = fs::File::create(output_path)
+        .with_context(|| format!("could not create {}", output_path.display()))?;
+    let mut writer = std::io::BufWriter::new(output_file);
+
+    write_content(&mut writer)
+        .and_then(|()| writer.flush())
+        .with_context(|| format!("could not write {}", output_path.display()))
+}
+
+// Function to build the graphviz arguments selecting the output format; svgz is
+// rendered as svg and compressed afterwards, as graphviz can only write it when it
+// was built with zlib
+fn dot_arguments(
+    image_format: ImageFormat,
+) -> Vec<String> {
+    match image_format {
+        ImageFormat::Svgz => vec!["-Tsvg".to_string()],
+        image_format => vec![format!("-T{}", image_format.extension())],
+    }
+}
+
+// Function to gzip-compress a rendered svg into an svgz image
+fn compress_svg(
+    svg_content: &[u8],
+) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(svg_content)?;
+    encoder.finish()
+}
+
+// Function to render dot content to an image file with graphviz, streaming the
+// dot source into its stdin as it is written
+pub fn render_graph(
+    write_dot: impl FnOnce(&mut dyn Write) -> std::io::Result<()> + Send,
+    image_format: ImageFormat,
+    output_path: &Path,
+    dot_bin: &str,
+    timeout: Option<Duration>,
+) -> anyhow::Result<()> {
+    info!("Generating {} file...", image_format.extension());
+
+    let image_content = run_graphviz(write_dot, image_format, dot_bin, timeout)?;
+
+    fs::write(output_path, image_content)
+        .with_context(|| format!("could not write {}", output_path.display()))
+}
+
+// Function to render dot content with graphviz, returning the rendered image; with a
+// timeout, graphviz is killed once it has run that long, and when it fails its error
+// output is reported instead of returning whatever image it produced
+pub fn run_graphviz(
+    write_dot: impl FnOnce(&mut dyn Write) -> std::io::Result<()> + Send,
+    image_format: ImageFormat,
+    dot_bin: &str,
+    timeout: Option<Duration>,
+) -> anyhow::Result<Vec<u8>> {
+    let started = Instant::now();
+
+    let mut dot_process = match Command::new(dot_bin)
+        .args(dot_arguments(image_format))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn() {
+        Ok(dot_process) => dot_process,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            bail!(
+                "graphviz `{}` not found on PATH; install it, point --dot-bin (or DOT_BIN) at it, \
+                 or use --dot-out or --format mermaid/graphml/csv instead",
+                dot_bin,
+            );
+        }
+        Err(error) => {
+            bail!("could not run graphviz `{}`: {}", dot_bin, error);
+        }
+    };
+
+    // the image and the error output are read on their own threads, so a full pipe
+    // can't stall graphviz while the graph is sent or it is waited for
+    let read_pipe = |pipe: Option<Box<dyn std::io::Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut content = Vec::new();
+
+            if let Some(mut pipe) = pipe {
+                pipe.read_to_end(&mut content).map(|_| content)
+            } else {
+                Ok(content)
+            }
+        })
+    };
+
+    let image_reader = read_pipe(dot_process.stdout.take().map(|pipe| Box::new(pipe) as _));
+    let error_reader = read_pipe(dot_process.stderr.take().map(|pipe| Box::new(pipe) as _));
+
+    let stdin = dot_process.stdin.take();
+
+    let (status, sent) = std::thread::scope(|scope| {
+        // the graph is sent on its own thread, so the timeout also covers a graphviz
+        // that stops reading it; a graphviz that exits early, or is killed, breaks the
+        // pipe, which its exit status below explains better
+        let sender = scope.spawn(move || match stdin {
+            Some(stdin) => {
+                let mut stdin = std::io::BufWriter::new(stdin);
+
+                write_dot(&mut stdin).and_then(|()| stdin.flush())
+            }
+            None => Ok(()),
+        });
+
+        let status = wait_for_graphviz(&mut dot_process, dot_bin, timeout, started);
+        let sent = sender.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+
+        (status, sent)
+    });
+
+    let status = status?;
+
+    let error_output = error_reader
+        .join()
+        .ok()
+        .and_then(Result::ok)
+        .map(|error_output| String::from_utf8_lossy(&error_output).trim_end().to_string())
+        .unwrap_or_default();
+
+    if !status.success() {
+        if error_output.is_empty() {
+            bail!("graphviz `{}` failed with {}", dot_bin, status);
+        }
+
+        bail!("graphviz `{}` failed with {}:\n{}", dot_bin, status, error_output);
+    }
+
+    // warnings of a successful run are passed on as graphviz printed them
+    if !error_output.is_empty() {
+        warn!("graphviz `{}` reported:\n{}", dot_bin, error_output);
+    }
+
+    sent.with_context(|| format!("could not send the graph to graphviz `{}`", dot_bin))?;
+
+    let image_content = image_reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("reading the output of graphviz `{}` failed", dot_bin))?
+        .with_context(|| format!("could not read the output of graphviz `{}`", dot_bin))?;
+
+    match image_format {
+        ImageFormat::Svgz => compress_svg(&image_content).context("could not compress the svg"),
+        _ => Ok(image_content),
+    }
+}
+
+// Function to wait for graphviz to exit, killing it once it has run for the timeout
+// counted from when it was started
+fn wait_for_graphviz(
+    dot_process: &mut Child,
+    dot_bin: &str,
+    timeout: Option<Duration>,
+    started: Instant,
+) -> anyhow::Result<ExitStatus> {
+    let Some(timeout) = timeout else {
+        return dot_process.wait()
+            .with_context(|| format!("could not run graphviz `{}`", dot_bin));
+    };
+
+    loop {
+        if let Some(status) = dot_process.try_wait()
+            .with_context(|| format!("could not run graphviz `{}`", dot_bin))? {
+            return Ok(status);
+        }
+
+        if started.elapsed() >= timeout {
+            let _ = dot_process.kill();
+            let _ = dot_process.wait();
+
+            bail!(
+                "graphviz `{}` did not finish within {} seconds; narrow the graph down with \
+                 --class-prefix, --depth or --node-budget, or raise --timeout",
+                dot_bin,
+                timeout.as_secs(),
+            );
+        }
+
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum ImageFormat {
+    #[serde(rename = "svg")]
+    Svg,
+    #[serde(rename = "svgz")]
+    Svgz,
+    #[serde(rename = "png")]
+    Png,
+    #[serde(rename = "pdf")]
+    Pdf,
+    #[serde(rename = "dot")]
+    Dot,
+}
+
+impl ImageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Svg => "svg",
+            ImageFormat::Svgz => "svgz",
+            ImageFormat::Png => "png",
+            ImageFormat::Pdf => "pdf",
+            ImageFormat::Dot => "dot",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "svg" => Ok(ImageFormat::Svg),
+            "svgz" => Ok(ImageFormat::Svgz),
+            "png" => Ok(ImageFormat::Png),
+            "pdf" => Ok(ImageFormat::Pdf),
+            "dot" => Ok(ImageFormat::Dot),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::test_support::{fake_dot, fixture};
+
+    #[cfg(unix)]
+    #[test]
+    fn graphviz_that_stops_reading_is_killed_on_timeout() {
+        let folder = fixture("graphviz-timeout", &[]);
+
+        // never reads the graph, so sending one larger than the pipe buffer blocks
+        let dot_bin = fake_dot(&folder, "exec sleep 30");
+
+        let started = Instant::now();
+
+        let rendered = run_graphviz(
+            |writer| {
+                for index in 0..100_000 {
+                    writeln!(writer, "  \"a{}\" -> \"b{}\";", index, index)?;
+                }
+                Ok(())
+            },
+            ImageFormat::Svg,
+            dot_bin.to_str().unwrap(),
+            Some(Duration::from_secs(1)),
+        );
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        let error = rendered.unwrap_err().to_string();
+
+        assert!(error.contains("did not finish within 1 seconds"), "{}", error);
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn missing_graphviz_is_reported_instead_of_panicking() {
+        let rendered = run_graphviz(
+            |writer| writeln!(writer, "digraph G {{}}"),
+            ImageFormat::Svg,
+            "/nonexistent/dot",
+            None,
+        );
+
+        let error = rendered.unwrap_err().to_string();
+
+        assert!(error.starts_with("graphviz `/nonexistent/dot` not found on PATH"), "{}", error);
+    }
+
+    #[test]
+    fn image_formats_select_their_graphviz_output_type() {
+        assert_eq!(dot_arguments("png".parse().unwrap()), vec!["-Tpng"]);
+        assert_eq!(dot_arguments(ImageFormat::Svg), vec!["-Tsvg"]);
+        assert_eq!(dot_arguments(ImageFormat::Pdf), vec!["-Tpdf"]);
+        assert!("jpeg".parse::<ImageFormat>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_graphviz_is_reported_without_writing_an_image() {
+        let folder = fixture("graphviz-fail", &[]);
+
+        let dot_bin = fake_dot(&folder, "cat > /dev/null\necho '<svg partial'\necho 'syntax error in line 3' >&2\nexit 1");
+        let output_path = folder.join("graph.svg");
+
+        let rendered = render_graph(
+            |writer| writeln!(writer, "digraph G {{ a -> }}"),
+            ImageFormat::Svg,
+            &output_path,
+            dot_bin.to_str().unwrap(),
+            None,
+        );
+
+        let output_written = output_path.exists();
+        fs::remove_dir_all(&folder).unwrap();
+
+        let error = rendered.unwrap_err().to_string();
+
+        assert!(error.contains("syntax error in line 3"), "{}", error);
+        assert!(!output_written);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn svgz_images_are_rendered_as_svg_and_compressed() {
+        use std::io::Read;
+
+        assert_eq!("svgz".parse::<ImageFormat>().unwrap().extension(), "svgz");
+        assert_eq!(dot_arguments(ImageFormat::Svgz), vec!["-Tsvg"]);
+
+        let folder = fixture("graphviz-svgz", &[]);
+
+        // answers like a graphviz built without zlib, which only knows -Tsvg
+        let dot_bin = fake_dot(&folder, "cat > /dev/null\n[ \"$1\" = -Tsvg ] || exit 1\necho '<svg/>'");
+
+        let image_content = run_graphviz(
+            |writer| writeln!(writer, "digraph G {{}}"),
+            ImageFormat::Svgz,
+            dot_bin.to_str().unwrap(),
+            None,
+        );
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        let mut svg_content = String::new();
+        flate2::read::GzDecoder::new(image_content.unwrap().as_slice())
+            .read_to_string(&mut svg_content)
+            .unwrap();
+
+        assert_eq!(svg_content, "<svg/>\n");
+    }
+}
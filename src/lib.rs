@@ -0,0 +1,109 @@
+// Package and import declarations read from java, kotlin, scala and groovy sources
+pub mod parse;
+
+// Walking folders, archives and file lists for the source files to parse
+pub mod scan;
+
+// Parsed files kept between runs while unchanged
+pub mod cache;
+
+// Rewrites of the map from packages to their imports
+pub mod transform;
+
+// The part of the graph reachable from the root packages
+pub mod tree;
+
+// Cycles, paths, metrics and other questions about the graph
+pub mod analysis;
+
+// Dot, mermaid, graphml, csv, tree and html output of the graph
+pub mod render;
+
+// Rendering dot sources into images with graphviz
+pub mod graphviz;
+
+// Fixtures for the unit tests
+#[cfg(test)]
+mod test_support;
+
+pub use parse::{extract_imports, extract_package, Import, ImportKind};
+pub use render::{generate_dot_content, write_dot_content, GraphStyle, RankDir};
+pub use scan::traverse_folder_par;
+pub use tree::{build_dependency_tree, TreeOptions};
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use dashmap::DashMap;
+
+use crate::scan::WalkFilter;
+use crate::transform::sorted_edges;
+
+/// The package dependency graph of a folder of java or kotlin files, mapping every
+/// package to the classes and packages it imports.
+///
+/// ```
+/// use jadep::DependencyGraph;
+///
+/// let folder = std::env::temp_dir().join(format!("jadep-doctest-{}", std::process::id()));
+/// std::fs::create_dir_all(&folder).unwrap();
+/// std::fs::write(
+///     folder.join("App.java"),
+///     "package com.acme.app;\nimport com.acme.core.Service;\n",
+/// ).unwrap();
+///
+/// let graph = DependencyGraph::from_folder(&folder);
+///
+/// assert_eq!(
+///     graph.edges(),
+///     vec![("com.acme.app".to_string(), "com.acme.core.Service".to_string())],
+/// );
+/// assert!(graph.to_dot().contains("\"com.acme.app\" -> \"com.acme.core.Service\""));
+///
+/// std::fs::remove_dir_all(&folder).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    pub imports_map: DashMap<String, Vec<String>>,
+}
+
+impl DependencyGraph {
+    /// Parses every java and kotlin file below a folder; unreadable entries are
+    /// skipped with a warning on stderr.
+    pub fn from_folder(folder_path: impl AsRef<Path>) -> DependencyGraph {
+        DependencyGraph {
+            imports_map: traverse_folder_par(
+                folder_path.as_ref().to_path_buf(),
+                None,
+                &WalkFilter::default(),
+                None,
+                None,
+            ),
+        }
+    }
+
+    /// Keeps the packages starting with the prefix and everything they depend on,
+    /// directly or not.
+    pub fn filter_by_prefix(&self, prefix: &str) -> DependencyGraph {
+        DependencyGraph {
+            imports_map: build_dependency_tree(&self.imports_map, Some(prefix), &TreeOptions::default()),
+        }
+    }
+
+    /// Generates the graphviz dot source of the graph, styled like the jadep binary's.
+    pub fn to_dot(&self) -> String {
+        generate_dot_content(
+            &self.imports_map,
+            None,
+            &TreeOptions::default(),
+            &HashMap::new(),
+            &HashMap::new(),
+            &GraphStyle::default(),
+        )
+    }
+
+    /// Lists every (package, import) edge, sorted.
+    pub fn edges(&self) -> Vec<(String, String)> {
+        sorted_edges(self.imports_map.clone())
+    }
+}
@@ -0,0 +1,952 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::scan::DEFAULT_PACKAGE;
+
+// Languages whose source files are parsed unless --lang picks others
+pub const DEFAULT_LANGUAGES: [Language; 2] = [Language::Java, Language::Kotlin];
+
+// File name of java 9+ module declarations, parsed for their requires directives
+const MODULE_DESCRIPTOR: &str = "module-info.java";
+
+// Number of built-in regexes compiled so far, letting tests check they aren't
+// compiled again for every file
+#[cfg(test)]
+static COMPILED_REGEXES: AtomicUsize = AtomicUsize::new(0);
+
+// Function to compile one of the built-in regexes
+fn compile_built_in_regex(
+    pattern: &str,
+) -> regex::Regex {
+    #[cfg(test)]
+    COMPILED_REGEXES.fetch_add(1, Ordering::Relaxed);
+
+    regex::Regex::new(pattern).unwrap()
+}
+
+// Regexes of the parsed declarations, compiled once on first use rather than for
+// every file; kotlin declarations have no trailing semicolon, and names may wrap
+// around their dots
+static PACKAGE_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    compile_built_in_regex(r"\bpackage\s+([\w$]+(?:\s*\.\s*[\w$]+)*)\s*;?")
+});
+
+static IMPORT_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    compile_built_in_regex(r"\bimport\s+(static\s+)?([\w$]+(?:\s*\.\s*(?:[\w$]+|\*))*)\s*;?")
+});
+
+static SCALA_IMPORT_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    compile_built_in_regex(r"\bimport\s+")
+});
+
+static MODULE_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    compile_built_in_regex(r"\bmodule\s+([\w$]+(?:\s*\.\s*[\w$]+)*)\s*\{")
+});
+
+static REQUIRES_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    compile_built_in_regex(r"\brequires\s+(?:(?:transitive|static)\s+)*([\w$]+(?:\s*\.\s*[\w$]+)*)\s*;")
+});
+
+static CLASS_DECLARATION_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    compile_built_in_regex(r"\b(?:class|interface|enum|record|object)\s+([A-Za-z_$][\w$]*)")
+});
+
+static USED_CLASS_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    compile_built_in_regex(r"\b([A-Z][\w$]*)\b")
+});
+
+// Function to blank out comments and string/char literals so that text inside
+// them is never mistaken for a package or import declaration; newlines are
+// kept so line numbers stay the same
+pub(crate) fn strip_comments_and_literals(
+    file_content: &str,
+) -> String {
+    let mut stripped = String::with_capacity(file_content.len());
+    let mut chars = file_content.chars().peekable();
+
+    // replaces a character that is being removed, keeping line breaks
+    let blank = |c: char| if c == '\n' { '\n' } else { ' ' };
+
+    while let Some(c) = chars.next() {
+        match c {
+            '/' if chars.peek() == Some(&'/') => {
+                stripped.push(' ');
+
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    stripped.push(' ');
+                    chars.next();
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                stripped.push(' ');
+                stripped.push(' ');
+                chars.next();
+
+                let mut previous = ' ';
+
+                for next in chars.by_ref() {
+                    stripped.push(blank(next));
+
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            '"' | '\'' => {
+                let quote = c;
+                stripped.push(' ');
+
+                // java text blocks are delimited by three double quotes
+                let text_block = quote == '"' && {
+                    let mut lookahead = chars.clone();
+                    lookahead.next() == Some('"') && lookahead.next() == Some('"')
+                };
+
+                if text_block {
+                    chars.next();
+                    chars.next();
+                    stripped.push_str("  ");
+                }
+
+                let mut closing_quotes = 0;
+
+                while let Some(next) = chars.next() {
+                    stripped.push(blank(next));
+
+                    if next == '\\' {
+                        if let Some(escaped) = chars.next() {
+                            stripped.push(blank(escaped));
+                        }
+                        closing_quotes = 0;
+                        continue;
+                    }
+
+                    if next == quote {
+                        closing_quotes += 1;
+
+                        if !text_block || closing_quotes == 3 {
+                            break;
+                        }
+                    } else {
+                        closing_quotes = 0;
+
+                        // unterminated literals end at the line break
+                        if next == '\n' && !text_block {
+                            break;
+                        }
+                    }
+                }
+            }
+            _ => stripped.push(c),
+        }
+    }
+
+    stripped
+}
+
+// Function to extract "package <some.value>;"
+pub fn extract_package(
+    file_path: &Path,
+) -> Option<String> {
+    extract_package_declarations(file_path, &ParseOptions::default())?.into_iter().next()
+}
+
+// Function to extract every "package <some.value>;" of a file; a well-formed file has at most one
+pub(crate) fn extract_package_declarations(
+    file_path: &Path,
+    parse_options: &ParseOptions,
+) -> Option<Vec<String>> {
+    package_declarations(&read_source(file_path)?, parse_options)
+}
+
+// Function to extract every "package <some.value>;" of source text
+fn package_declarations(
+    file_content: &str,
+    parse_options: &ParseOptions,
+) -> Option<Vec<String>> {
+    // a custom regex sees the source as written, so it can match inside strings
+    let (package_regex, file_content) =
+        match parse_options.package_regex {
+            Some(ref package_regex) => (package_regex, file_content.to_string()),
+            None => (&*PACKAGE_REGEX, strip_comments_and_literals(file_content)),
+        };
+
+    Some(
+        package_regex
+            .captures_iter(&file_content)
+            .filter_map(|captures| captures.get(1))
+            .map(|package_name| remove_whitespace(package_name.as_str()))
+            .collect()
+    )
+}
+
+// Function to check whether a name is a dotted java identifier like com.foo.Bar,
+// optionally ending in a .* wildcard
+fn is_dotted_identifier(
+    name: &str,
+) -> bool {
+    let segments: Vec<&str> = name.split('.').collect();
+
+    segments.iter().enumerate().all(|(index, segment)| {
+        if index > 0 && index == segments.len() - 1 && *segment == "*" {
+            return true;
+        }
+
+        let mut chars = segment.chars();
+
+        chars
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    })
+}
+
+// Function to warn about a file that parses, but not cleanly; --strict turns
+// any such warning into an error once parsing is done
+fn report_parse_problem(
+    parse_options: &ParseOptions,
+    location: &str,
+    problem: &str,
+) {
+    warn!("{}: {}", location, problem);
+    parse_options.problems.fetch_add(1, Ordering::Relaxed);
+}
+
+// Function to report the parse problems a cached file had when it was parsed, so a
+// cache hit fails a --strict run just like parsing the file again would
+pub(crate) fn report_cached_parse_problems(
+    parse_options: &ParseOptions,
+    location: &str,
+    problems: usize,
+) {
+    warn!("{}: {} parse problems when it was cached, run without --cache to see them", location, problems);
+    parse_options.problems.fetch_add(problems, Ordering::Relaxed);
+}
+
+// Function to extract "module <some.value> { requires <other.value>; }" from the
+// text of a module-info.java file, returning the module and the modules it requires
+// along with their line numbers
+fn module_requires(
+    file_content: &str,
+) -> Option<SourceFile> {
+    let file_content = strip_comments_and_literals(file_content);
+
+    let captures = MODULE_REGEX.captures(&file_content)?;
+    let module_name = remove_whitespace(captures.get(1)?.as_str());
+
+    let requires = REQUIRES_REGEX
+        .captures_iter(&file_content)
+        .filter_map(|captures| captures.get(1))
+        .map(|required_module| {
+            let line_number = file_content[..required_module.start()].matches('\n').count() + 1;
+            let target = remove_whitespace(required_module.as_str());
+
+            (line_number, Import { target, kind: ImportKind::Normal })
+        })
+        .collect();
+
+    Some(SourceFile { package: module_name, imports: requires, ..SourceFile::default() })
+}
+
+// Function to check whether a file is a module-info.java module declaration
+pub(crate) fn is_module_descriptor(
+    file_path: &Path,
+) -> bool {
+    file_path
+        .file_name()
+        .is_some_and(|file_name| file_name == MODULE_DESCRIPTOR)
+}
+
+// Function to drop the whitespace a declaration may contain when it is wrapped
+fn remove_whitespace(
+    text: &str,
+) -> String {
+    text.split_whitespace().collect()
+}
+
+// Function to extract all "import [static] <some.value>;"
+pub fn extract_imports(
+    file_path: &Path,
+) -> Option<Vec<Import>> {
+    Some(
+        extract_import_lines(file_path, &ParseOptions::default())?
+            .into_iter()
+            .map(|(_, import)| import)
+            .collect()
+    )
+}
+
+// Function to extract all "import [static] <some.value>;" along with their line numbers
+pub(crate) fn extract_import_lines(
+    file_path: &Path,
+    parse_options: &ParseOptions,
+) -> Option<Vec<(usize, Import)>> {
+    source_import_lines(file_path, &read_source(file_path)?, parse_options)
+}
+
+// Function to extract all "import [static] <some.value>;" of source text along with their line numbers
+fn import_lines(
+    file_content: &str,
+    parse_options: &ParseOptions,
+) -> Option<Vec<(usize, Import)>> {
+    // a custom regex only captures the name, so its imports are never static, and it
+    // sees the source as written, so it can match inside strings
+    let (import_regex, static_group, target_group, file_content) =
+        match parse_options.import_regex {
+            Some(ref import_regex) => (import_regex, None, 1, file_content.to_string()),
+            None => (&*IMPORT_REGEX, Some(1), 2, strip_comments_and_literals(file_content)),
+        };
+
+    let mut line_number = 1;
+    let mut line_position = 0;
+
+    Some(
+        import_regex
+            .captures_iter(&file_content)
+            .filter_map(|captures| {
+                let start = captures.get(0).unwrap().start();
+
+                line_number += file_content[line_position..start].matches('\n').count();
+                line_position = start;
+
+                let target = remove_whitespace(captures.get(target_group)?.as_str());
+
+                let kind = if static_group.is_some_and(|static_group| captures.get(static_group).is_some()) {
+                    ImportKind::Static
+                } else if target.ends_with(".*") {
+                    ImportKind::Wildcard
+                } else {
+                    ImportKind::Normal
+                };
+
+                Some((line_number, Import { target, kind }))
+            })
+            .collect()
+    )
+}
+
+// Function to extract the imports of scala source text along with their line numbers;
+// an import clause may list several selectors, and every member of a brace group
+// like "import a.b.{C, D => E, _}" becomes an import of its own
+fn scala_import_lines(
+    file_content: &str,
+) -> Option<Vec<(usize, Import)>> {
+    let file_content = strip_comments_and_literals(file_content);
+
+    let mut import_lines = Vec::new();
+    let mut line_number = 1;
+    let mut line_position = 0;
+
+    for import_match in SCALA_IMPORT_REGEX.find_iter(&file_content) {
+        line_number += file_content[line_position..import_match.start()].matches('\n').count();
+        line_position = import_match.start();
+
+        // without semicolons the clause ends with its line, unless a brace group is open
+        let mut depth = 0;
+        let mut clause_end = import_match.end();
+
+        for (offset, c) in file_content[import_match.end()..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                '\n' | ';' if depth <= 0 => break,
+                _ => {}
+            }
+
+            clause_end = import_match.end() + offset + c.len_utf8();
+        }
+
+        let mut targets = Vec::new();
+
+        for selector in split_top_level(&file_content[import_match.end()..clause_end]) {
+            match selector.split_once('{') {
+                Some((prefix, members)) => {
+                    let prefix = remove_whitespace(prefix);
+
+                    for member in members.trim_end().trim_end_matches('}').split(',') {
+                        if let Some(member) = scala_selector_name(member) {
+                            targets.push(format!("{}{}", prefix, member));
+                        }
+                    }
+                }
+                None => {
+                    if let Some(target) = scala_selector_name(selector) {
+                        targets.push(target);
+                    }
+                }
+            }
+        }
+
+        for target in targets {
+            let kind = if target.ends_with(".*") { ImportKind::Wildcard } else { ImportKind::Normal };
+            import_lines.push((line_number, Import { target, kind }));
+        }
+    }
+
+    Some(import_lines)
+}
+
+// Function to split a scala import clause at the commas outside brace groups
+fn split_top_level(
+    clause: &str,
+) -> Vec<&str> {
+    let mut selectors = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (position, c) in clause.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                selectors.push(&clause[start..position]);
+                start = position + 1;
+            }
+            _ => {}
+        }
+    }
+
+    selectors.push(&clause[start..]);
+
+    selectors
+        .into_iter()
+        .filter(|selector| !selector.trim().is_empty())
+        .collect()
+}
+
+// Function to get the imported name of a scala selector, dropping a rename; hidden
+// members ("C => _") and given imports bring in no name, and "_" is a wildcard
+fn scala_selector_name(
+    selector: &str,
+) -> Option<String> {
+    let (name, rename) =
+        match selector.split_once("=>").or_else(|| selector.split_once(" as ")) {
+            Some((name, rename)) => (name, Some(rename.trim())),
+            None => (selector, None),
+        };
+
+    let name = remove_whitespace(name);
+
+    if rename == Some("_") || name.is_empty() || name == "given" || name.ends_with(".given") {
+        return None;
+    }
+
+    match name.strip_suffix('_') {
+        Some(prefix) if prefix.is_empty() || prefix.ends_with('.') => Some(format!("{}*", prefix)),
+        _ => Some(name),
+    }
+}
+
+// Function to extract the imports of source text in the language of its file
+fn source_import_lines(
+    file_path: &Path,
+    file_content: &str,
+    parse_options: &ParseOptions,
+) -> Option<Vec<(usize, Import)>> {
+    match Language::of_file(file_path) {
+        Some(Language::Scala) if parse_options.import_regex.is_none() => scala_import_lines(file_content),
+        _ => import_lines(file_content, parse_options),
+    }
+}
+
+// Function to decode the bytes of a source file, dropping a UTF-8 byte order mark;
+// invalid UTF-8 is replaced, which is reported back so callers can warn about it
+fn decode_source(
+    file_bytes: &[u8],
+    encoding: SourceEncoding,
+) -> (String, bool) {
+    let file_bytes = file_bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(file_bytes);
+
+    match encoding {
+        // every ISO-8859-1 byte is the code point of the same value
+        SourceEncoding::Latin1 => (file_bytes.iter().map(|&byte| byte as char).collect(), false),
+        SourceEncoding::Utf8 => match std::str::from_utf8(file_bytes) {
+            Ok(file_content) => (file_content.to_string(), false),
+            Err(_) => (String::from_utf8_lossy(file_bytes).into_owned(), true),
+        },
+    }
+}
+
+// Function to read a source file as UTF-8 text, dropping a byte order mark and
+// replacing invalid UTF-8 without a warning
+pub(crate) fn read_source(
+    file_path: &Path,
+) -> Option<String> {
+    Some(decode_source(&fs::read(file_path).ok()?, SourceEncoding::Utf8).0)
+}
+
+// Function to decode the bytes of a source file, warning when a file that is
+// expected to be UTF-8 isn't, since names with replaced characters may be wrong
+pub(crate) fn decode_source_reporting(
+    file_path: &Path,
+    file_bytes: &[u8],
+    parse_options: &ParseOptions,
+) -> String {
+    let (file_content, lossy) = decode_source(file_bytes, parse_options.encoding);
+
+    if lossy {
+        report_parse_problem(
+            parse_options,
+            &file_path.display().to_string(),
+            "not valid UTF-8, replacing the invalid bytes; pass --encoding latin1 for ISO-8859-1 sources",
+        );
+    }
+
+    file_content
+}
+
+// Function to extract the package and imports of a single source file
+pub(crate) fn parse_package_imports(
+    file_path: &Path,
+    parse_options: &ParseOptions,
+) -> Option<SourceFile> {
+    let file_bytes = match fs::read(file_path) {
+        Ok(file_bytes) => file_bytes,
+        Err(error) => {
+            report_parse_problem(
+                parse_options,
+                &file_path.display().to_string(),
+                &format!("could not be read: {}", error),
+            );
+            return None;
+        }
+    };
+
+    parse_source_text(file_path, &decode_source_reporting(file_path, &file_bytes, parse_options), parse_options)
+}
+
+// Function to extract the package and imports of the text of a source file,
+// which may have been read from an archive rather than from its own file
+pub(crate) fn parse_source_text(
+    file_path: &Path,
+    file_content: &str,
+    parse_options: &ParseOptions,
+) -> Option<SourceFile> {
+    // a module declaration has no package; its required modules become the edges
+    if is_module_descriptor(file_path) {
+        return module_requires(file_content);
+    }
+
+    let location = file_path.display().to_string();
+    let import_lines = source_import_lines(file_path, file_content, parse_options)?;
+
+    // a malformed import would become a node no package or class has
+    let imports: Vec<(usize, Import)> =
+        import_lines
+            .into_iter()
+            .filter(|(line_number, import)| {
+                let is_valid = is_dotted_identifier(&import.target);
+
+                if !is_valid {
+                    report_parse_problem(
+                        parse_options,
+                        &format!("{}:{}", location, line_number),
+                        &format!("malformed import {}, skipping it", import.target),
+                    );
+                }
+
+                is_valid
+            })
+            .collect();
+
+    let package_names = package_declarations(file_content, parse_options).unwrap_or_default();
+
+    if package_names.len() > 1 && Language::of_file(file_path) != Some(Language::Scala) {
+        report_parse_problem(parse_options, &location, "more than one package declaration, using the first");
+    }
+
+    if let Some(package_name) = package_names.first().filter(|package_name| !is_dotted_identifier(package_name)) {
+        report_parse_problem(parse_options, &location, &format!("malformed package declaration {}", package_name));
+    }
+
+    // chained scala package clauses ("package a" then "package b") nest into a.b
+    let package_names =
+        if Language::of_file(file_path) == Some(Language::Scala) && package_names.len() > 1 {
+            vec![package_names.join(".")]
+        } else {
+            package_names
+        };
+
+    // files without a package declaration keep their edges in the default package
+    let package_name = package_names.into_iter().next().unwrap_or_else(|| {
+        debug!("No package declaration in {}, using {}", file_path.display(), DEFAULT_PACKAGE);
+        DEFAULT_PACKAGE.to_string()
+    });
+
+    let file_content = strip_comments_and_literals(file_content);
+    let declared_classes = extract_declared_classes(&file_content);
+
+    // only the classes used by files with wildcard imports are ever looked up
+    let used_classes =
+        if imports.iter().any(|(_, import)| import.kind == ImportKind::Wildcard) {
+            extract_used_classes(&file_content)
+        } else {
+            Vec::new()
+        };
+
+    Some(SourceFile { package: package_name, imports, declared_classes, used_classes })
+}
+
+// Function to extract the simple names of all classes, interfaces, enums and
+// records declared in a file, sorted
+fn extract_declared_classes(
+    file_content: &str,
+) -> Vec<String> {
+    let declared_classes: BTreeSet<String> = CLASS_DECLARATION_REGEX
+        .captures_iter(file_content)
+        .map(|captures| captures[1].to_string())
+        .collect();
+
+    declared_classes.into_iter().collect()
+}
+
+// Function to extract the capitalized names a file refers to, taken to be the
+// simple names of the classes it uses, sorted
+fn extract_used_classes(
+    file_content: &str,
+) -> Vec<String> {
+    let used_classes: BTreeSet<String> = USED_CLASS_REGEX
+        .captures_iter(file_content)
+        .map(|captures| captures[1].to_string())
+        .collect();
+
+    used_classes.into_iter().collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportKind {
+    // import com.foo.Bar;
+    Normal,
+    // import static com.foo.Bar.method; or import static com.foo.Bar.*;
+    Static,
+    // import com.foo.*;
+    Wildcard,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Import {
+    pub target: String,
+    pub kind: ImportKind,
+}
+
+// Package and imports parsed from a source file, each import along with the line
+// it is on, and the simple names of the classes the file declares; the names of
+// the classes it uses are only kept when it has a wildcard import to resolve
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SourceFile {
+    pub package: String,
+    pub imports: Vec<(usize, Import)>,
+    pub declared_classes: Vec<String>,
+    pub used_classes: Vec<String>,
+}
+
+impl SourceFile {
+    // Function to get the names the file imports, in the order they are imported
+    pub fn import_targets(&self) -> Vec<String> {
+        self.imports
+            .iter()
+            .map(|(_, import)| import.target.clone())
+            .collect()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
+pub enum SourceEncoding {
+    #[serde(rename = "utf8")]
+    #[default]
+    Utf8,
+    #[serde(rename = "latin1")]
+    Latin1,
+}
+
+impl FromStr for SourceEncoding {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "utf8" => Ok(SourceEncoding::Utf8),
+            "latin1" => Ok(SourceEncoding::Latin1),
+            _ => Err(()),
+        }
+    }
+}
+
+// How the bytes of source files are decoded and which regexes, given with
+// --package-regex and --import-regex, replace the built-in ones; their first capture
+// group holds the package or import name. It also counts the parse problems reported
+// while parsing, which --strict checks once a run is done
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub encoding: SourceEncoding,
+    pub package_regex: Option<regex::Regex>,
+    pub import_regex: Option<regex::Regex>,
+    pub problems: Arc<AtomicUsize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Language {
+    #[serde(rename = "java")]
+    Java,
+    #[serde(rename = "kotlin")]
+    Kotlin,
+    #[serde(rename = "scala")]
+    Scala,
+    #[serde(rename = "groovy")]
+    Groovy,
+}
+
+impl Language {
+    // Function to get the language of a source file from its extension
+    pub fn of_file(file_path: &Path) -> Option<Language> {
+        match file_path.extension()?.to_str()? {
+            "java" => Some(Language::Java),
+            "kt" => Some(Language::Kotlin),
+            "scala" => Some(Language::Scala),
+            "groovy" => Some(Language::Groovy),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "java" => Ok(Language::Java),
+            "kotlin" => Ok(Language::Kotlin),
+            "scala" => Ok(Language::Scala),
+            "groovy" => Ok(Language::Groovy),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_problems_are_counted_per_run() {
+        let first_run = ParseOptions::default();
+        let second_run = ParseOptions::default();
+
+        parse_source_text(Path::new("A.java"), "package a;\npackage b;\n", &first_run);
+        parse_source_text(Path::new("A.java"), "package a;\nimport b.C;\n", &second_run);
+
+        assert_eq!(first_run.problems.load(Ordering::Relaxed), 1);
+        assert_eq!(second_run.problems.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn imports_are_tagged_as_static_wildcard_or_normal() {
+        let imports: Vec<Import> = import_lines(
+            "package com.acme;\n\
+             import static org.junit.Assert.assertEquals;\n\
+             import java.util.*;\n\
+             import java.util.List;\n\
+             import static org.junit.Assert.*;\n",
+            &ParseOptions::default(),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|(_, import)| import)
+        .collect();
+
+        assert_eq!(
+            imports,
+            vec![
+                Import { target: "org.junit.Assert.assertEquals".to_string(), kind: ImportKind::Static },
+                Import { target: "java.util.*".to_string(), kind: ImportKind::Wildcard },
+                Import { target: "java.util.List".to_string(), kind: ImportKind::Normal },
+                Import { target: "org.junit.Assert.*".to_string(), kind: ImportKind::Static },
+            ],
+        );
+    }
+
+    #[test]
+    fn declarations_in_comments_and_strings_are_ignored() {
+        let source = parse_source_text(
+            Path::new("App.java"),
+            "/* package com.fake;\n\
+             import com.fake.Block; */\n\
+             package com.acme.app;\n\
+             // import com.fake.Line;\n\
+             import com.acme.core.Service;\n\
+             class App { String s = \"import com.fake.Text;\"; }\n",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(source.package, "com.acme.app");
+        assert_eq!(source.import_targets(), vec!["com.acme.core.Service".to_string()]);
+        assert_eq!(source.imports[0].0, 5);
+    }
+
+    #[test]
+    fn kotlin_declarations_without_semicolons_are_parsed() {
+        let source = parse_source_text(
+            Path::new("App.kt"),
+            "package com.acme.app\n\
+             \n\
+             import com.acme.core.Service\n\
+             import kotlinx.coroutines.*\n\
+             \n\
+             class App(val service: Service)\n",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(source.package, "com.acme.app");
+        assert_eq!(source.import_targets(), vec!["com.acme.core.Service", "kotlinx.coroutines.*"]);
+    }
+
+    #[test]
+    fn wrapped_imports_and_trailing_comments_are_parsed() {
+        let source = parse_source_text(
+            Path::new("App.java"),
+            "package com.acme.app;\n\
+             import\n    com.acme.core.Service;\n\
+             import com.acme.core.Repository; // trailing\n\
+             import jakarta.annotation.Nonnull;\n",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            source.import_targets(),
+            vec![
+                "com.acme.core.Service".to_string(),
+                "com.acme.core.Repository".to_string(),
+                "jakarta.annotation.Nonnull".to_string(),
+            ],
+        );
+        assert_eq!(source.imports[0].0, 2);
+    }
+
+    #[test]
+    fn malformed_imports_are_dropped() {
+        let parse_options = ParseOptions {
+            import_regex: Some(regex::Regex::new(r"import\s+(\S+);").unwrap()),
+            ..ParseOptions::default()
+        };
+
+        let source = parse_source_text(
+            Path::new("App.java"),
+            "package com.acme.app;\n\
+             import com.acme.core.Service;\n\
+             import com.acme.core.Repository@Nonnull;\n\
+             import 9lives.Cat;\n\
+             import java.util.*;\n",
+            &parse_options,
+        )
+        .unwrap();
+
+        assert_eq!(
+            source.import_targets(),
+            vec!["com.acme.core.Service".to_string(), "java.util.*".to_string()],
+        );
+        assert_eq!(parse_options.problems.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn scala_grouped_imports_become_one_import_per_member() {
+        let source = parse_source_text(
+            Path::new("App.scala"),
+            "package com.acme.app\n\
+             import com.acme.core.{Service, Repository => Repo}\n\
+             import scala.collection.mutable\n",
+            &ParseOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(source.package, "com.acme.app");
+        assert_eq!(
+            source.import_targets(),
+            vec![
+                "com.acme.core.Service".to_string(),
+                "com.acme.core.Repository".to_string(),
+                "scala.collection.mutable".to_string(),
+            ],
+        );
+    }
+
+    #[test]
+    fn bom_and_latin1_sources_are_parsed() {
+        let folder = std::env::temp_dir().join(format!("jadep-encodings-{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+
+        let bom_path = folder.join("Bom.java");
+        fs::write(&bom_path, b"\xEF\xBB\xBFpackage com.acme.bom;\nimport com.acme.core.Service;\n").unwrap();
+
+        // "Caf\xE9" is "Café" in ISO-8859-1 and not valid UTF-8
+        let latin1_path = folder.join("Latin1.java");
+        fs::write(&latin1_path, b"package com.acme.caf\xE9;\nimport com.acme.core.Service;\n").unwrap();
+
+        let utf8 = ParseOptions::default();
+        let latin1 = ParseOptions { encoding: SourceEncoding::Latin1, ..ParseOptions::default() };
+
+        let bom_source = parse_package_imports(&bom_path, &utf8).unwrap();
+        let lossy_source = parse_package_imports(&latin1_path, &utf8).unwrap();
+        let latin1_source = parse_package_imports(&latin1_path, &latin1).unwrap();
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(bom_source.package, "com.acme.bom");
+        assert_eq!(lossy_source.import_targets(), vec!["com.acme.core.Service".to_string()]);
+        assert_eq!(utf8.problems.load(Ordering::Relaxed), 1);
+        assert_eq!(latin1_source.package, "com.acme.café");
+        assert_eq!(latin1.problems.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn custom_package_and_import_regexes_replace_the_built_in_ones() {
+        let parse_options = ParseOptions {
+            package_regex: Some(regex::Regex::new(r"@Module\(\s*([\w.]+)\s*\)").unwrap()),
+            import_regex: Some(regex::Regex::new(r"@Uses\(\s*([\w.]+)\s*\)").unwrap()),
+            ..ParseOptions::default()
+        };
+
+        let source = parse_source_text(
+            Path::new("App.java"),
+            "@Module(com.acme.app)\n\
+             @Uses(com.acme.core.Service)\n\
+             import com.acme.ignored.Thing;\n",
+            &parse_options,
+        )
+        .unwrap();
+
+        assert_eq!(source.package, "com.acme.app");
+        assert_eq!(source.import_targets(), vec!["com.acme.core.Service".to_string()]);
+        assert_eq!(source.imports[0].1.kind, ImportKind::Normal);
+    }
+
+    #[test]
+    fn built_in_regexes_are_compiled_once_for_every_file() {
+        for index in 0..100 {
+            let source = parse_source_text(
+                Path::new("App.java"),
+                &format!("package com.acme.p{};\nimport com.acme.core.Service;\nclass App {{}}\n", index),
+                &ParseOptions::default(),
+            )
+            .unwrap();
+
+            assert_eq!(source.package, format!("com.acme.p{}", index));
+        }
+
+        // at most one compilation for each of the seven built-in regexes, however
+        // many files were parsed
+        assert!(COMPILED_REGEXES.load(Ordering::Relaxed) <= 7);
+    }
+}
@@ -0,0 +1,1068 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Write;
+use std::str::FromStr;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::transform::{package_prefix, sorted_edges};
+use crate::tree::{build_dependency_tree, root_packages, TreeOptions};
+
+// Number of leading package segments whose nodes share a fill color
+const COLOR_GROUP_SEGMENTS: usize = 3;
+
+// Most source files listed in the tooltip of a node
+const TOOLTIP_FILES: usize = 20;
+
+// Function to build the tooltip attribute listing the source files of a node,
+// naming at most TOOLTIP_FILES of them
+pub fn files_tooltip(
+    files: &[String],
+) -> String {
+    let mut files = files.to_vec();
+    files.sort();
+
+    let mut lines = vec![format!("{} file{}", files.len(), if files.len() == 1 { "" } else { "s" })];
+    lines.extend(files.iter().take(TOOLTIP_FILES).cloned());
+
+    if files.len() > TOOLTIP_FILES {
+        lines.push(format!("... and {} more", files.len() - TOOLTIP_FILES));
+    }
+
+    format!("tooltip={}", escape_dot_string(&lines.join("\n")))
+}
+
+// Function to label every weighted edge with its weight, drawing heavier edges
+// up to five times as wide as an edge of weight one; orthogonal splines can't
+// place plain labels, so the weight is an external label
+pub fn weight_edge_attributes(
+    edge_weights: &HashMap<(String, String), usize>,
+) -> HashMap<(String, String), Vec<String>> {
+    let max_weight = edge_weights.values().copied().max().unwrap_or(1);
+
+    edge_weights
+        .iter()
+        .map(|(edge, &weight)| {
+            let penwidth = 1.0 + 4.0 * (weight - 1) as f64 / (max_weight - 1).max(1) as f64;
+
+            (
+                edge.clone(),
+                vec![
+                    format!("xlabel=\"{}\"", weight),
+                    format!("penwidth={:.2}", penwidth),
+                ],
+            )
+        })
+        .collect()
+}
+
+// Function to derive a fill color from the leading package segments of a name
+// (stopping at the first capitalized, i.e. class, segment), hashed with FNV-1a
+// so the same namespace gets the same color on every run
+fn namespace_fill_color(
+    package_name: &str,
+) -> String {
+    let namespace: Vec<&str> = package_name
+        .split('.')
+        .take_while(|segment| !segment.starts_with(|c: char| c.is_ascii_uppercase()))
+        .take(COLOR_GROUP_SEGMENTS)
+        .collect();
+
+    let hash = namespace
+        .join(".")
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+
+    format!("fillcolor=\"{:.3} 0.350 0.950\"", (hash % 1000) as f64 / 1000.0)
+}
+
+// Function to quote a string for dot; inside a quoted string only a backslash and
+// a double quote are special, and line breaks are kept as escapes so the string
+// stays on one line
+pub fn escape_dot_string(
+    value: &str,
+) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for character in value.chars() {
+        match character {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            character if character.is_control() => quoted.push(' '),
+            character => quoted.push(character),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+// Function to quote a package name as a dot ID; slashes become underscores as they
+// always have, keeping node IDs stable
+fn escape_dot_id(
+    package_name: &str,
+) -> String {
+    escape_dot_string(&package_name.replace('/', "_"))
+}
+
+// Function to write the dot content piece by piece, so huge graphs are streamed to
+// graphviz or a file instead of being assembled in memory first
+pub fn write_dot_content<W: Write + ?Sized>(
+    writer: &mut W,
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+    node_attributes: &HashMap<String, Vec<String>>,
+    edge_attributes: &HashMap<(String, String), Vec<String>>,
+    graph_style: &GraphStyle,
+) -> std::io::Result<()> {
+    writeln!(writer, "strict digraph G {{")?;
+
+    match graph_style.rank_dir {
+        RankDir::LR => writeln!(writer, "  rankdir=LR;")?,
+        RankDir::RL => writeln!(writer, "  rankdir=RL;")?,
+        RankDir::TB => writeln!(writer, "  rankdir=TB;")?,
+        RankDir::BT => writeln!(writer, "  rankdir=BT;")?,
+    }
+
+    // colors are passed through quoted, so graphviz reports any it doesn't know
+    writeln!(writer, "  graph [bgcolor={}];", escape_dot_string(&graph_style.bg_color))?;
+    writeln!(writer, "  graph [label=\"Orthogonal edges\", splines=ortho, nodesep=0.8];")?;
+    writeln!(
+        writer,
+        "  edge [color={0}, fontcolor={0}];",
+        escape_dot_string(&graph_style.edge_color),
+    )?;
+    writeln!(writer, "  graph[ratio=fill,center=1];")?;
+
+    match graph_style.node_color {
+        Some(ref node_color) => writeln!(
+            writer,
+            "  node[style=filled, shape=box, fillcolor={}];",
+            escape_dot_string(node_color)
+        )?,
+        None => writeln!(writer, "  node[style=filled, shape=box];")?,
+    }
+
+    // later attribute statements override the defaults above
+    for (statement, extra_attributes) in [
+        ("graph", &graph_style.extra_graph_attributes),
+        ("node", &graph_style.extra_node_attributes),
+        ("edge", &graph_style.extra_edge_attributes),
+    ] {
+        if !extra_attributes.is_empty() {
+            let attributes: Vec<String> = extra_attributes
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, escape_dot_string(value)))
+                .collect();
+
+            writeln!(writer, "  {} [{}];", statement, attributes.join(", "))?;
+        }
+    }
+
+    let dependency_tree =
+        build_dependency_tree(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        );
+
+    let mut package_names = BTreeSet::new();
+
+    for entry in dependency_tree.iter() {
+        package_names.insert(entry.key().to_string());
+        package_names.extend(entry.value().iter().cloned());
+    }
+
+    // the namespace fill is only used when no explicit fill color was given
+    let mut node_declarations = BTreeMap::<String, Vec<String>>::new();
+
+    for package_name in &package_names {
+        let mut attributes = node_attributes
+            .get(package_name)
+            .cloned()
+            .unwrap_or_default();
+
+        let has_fill = attributes
+            .iter()
+            .any(|attribute| attribute.starts_with("fillcolor="));
+
+        // a uniform node color replaces the per-namespace fills
+        if graph_style.color_nodes && graph_style.node_color.is_none() && !has_fill {
+            attributes.insert(0, namespace_fill_color(package_name));
+        }
+
+        node_declarations.insert(package_name.to_string(), attributes);
+    }
+
+    let declare_node = |writer: &mut W, indent: &str, package_name: &str, attributes: &[String]| {
+        if attributes.is_empty() {
+            writeln!(
+                writer,
+                "{}{};",
+                indent,
+                escape_dot_id(package_name)
+            )
+        } else {
+            writeln!(
+                writer,
+                "{}{} [{}];",
+                indent,
+                escape_dot_id(package_name),
+                attributes.join(", ")
+            )
+        }
+    };
+
+    if let Some(cluster_depth) = graph_style.cluster_depth {
+        // packages with fewer segments than the cluster depth stay at the top level
+        let mut clusters = BTreeMap::<&str, Vec<&String>>::new();
+
+        for package_name in &package_names {
+            if package_name.split('.').count() >= cluster_depth {
+                clusters
+                    .entry(package_prefix(package_name, cluster_depth))
+                    .or_default()
+                    .push(package_name);
+            } else {
+                declare_node(writer, "  ", package_name, &node_declarations[package_name])?;
+            }
+        }
+
+        for (cluster_id, (cluster_prefix, members)) in clusters.into_iter().enumerate() {
+            writeln!(writer, "  subgraph cluster_{} {{", cluster_id)?;
+            writeln!(
+                writer,
+                "    label={};\n    fontcolor={};\n    color=gray;",
+                escape_dot_string(cluster_prefix),
+                escape_dot_string(&graph_style.edge_color)
+            )?;
+
+            for package_name in members {
+                declare_node(writer, "    ", package_name, &node_declarations[package_name])?;
+            }
+
+            writeln!(writer, "  }}")?;
+        }
+    } else {
+        for (package_name, attributes) in &node_declarations {
+            if !attributes.is_empty() {
+                declare_node(writer, "  ", package_name, attributes)?;
+            }
+        }
+    }
+
+    for (package_name, import_value) in sorted_edges(dependency_tree) {
+        let attributes = edge_attributes
+            .get(&(package_name.clone(), import_value.clone()))
+            .map(|attributes| format!(" [{}]", attributes.join(", ")))
+            .unwrap_or_default();
+
+        writeln!(
+            writer,
+            "  {} -> {}{};",
+            escape_dot_id(&package_name),
+            escape_dot_id(&import_value),
+            attributes
+        )?;
+    }
+
+    write!(writer, "}}")
+}
+
+// Function to generate the dot content as a string
+pub fn generate_dot_content(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+    node_attributes: &HashMap<String, Vec<String>>,
+    edge_attributes: &HashMap<(String, String), Vec<String>>,
+    graph_style: &GraphStyle,
+) -> String {
+    let mut dot_content = Vec::new();
+
+    // writing to a vector can't fail, and the dot content is built from strings
+    write_dot_content(
+        &mut dot_content,
+        imports_map,
+        root_class_prefix,
+        tree_options,
+        node_attributes,
+        edge_attributes,
+        graph_style,
+    )
+    .expect("writing to memory failed");
+
+    String::from_utf8(dot_content).expect("dot content is valid UTF-8")
+}
+
+// Function to serialize the dependency tree in the requested graph format, where html
+// pages are generated from the dot content like images; mermaid and graphml have no
+// counterpart for the dot node and edge attributes, so they are dropped
+pub fn generate_graph_content(
+    graph_format: GraphFormat,
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+    node_attributes: &HashMap<String, Vec<String>>,
+    edge_attributes: &HashMap<(String, String), Vec<String>>,
+    graph_style: &GraphStyle,
+) -> String {
+    match graph_format {
+        GraphFormat::Dot | GraphFormat::Html => generate_dot_content(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+            node_attributes,
+            edge_attributes,
+            graph_style,
+        ),
+        GraphFormat::Mermaid => generate_mermaid_content(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+            graph_style.rank_dir,
+        ),
+        GraphFormat::Graphml => generate_graphml_content(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        ),
+        GraphFormat::Tree => generate_tree_content(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        ),
+        GraphFormat::Csv => generate_csv_content(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        ),
+    }
+}
+
+// Function to quote a csv field if it holds a comma, a double quote or a line break
+fn escape_csv(
+    field: &str,
+) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Function to generate a csv edge list of the dependency tree, one source,target row per edge
+fn generate_csv_content(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+) -> String {
+    let mut csv_content = String::from("source,target\n");
+
+    let dependency_tree =
+        build_dependency_tree(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        );
+
+    for (package_name, import_value) in sorted_edges(dependency_tree) {
+        csv_content += &format!("{},{}\n", escape_csv(&package_name), escape_csv(&import_value));
+    }
+
+    csv_content
+}
+
+// Function to render the dependency tree as indented text, one package per line
+// below the package importing it; a package already on the current chain is
+// marked "(cycle)" and one whose imports were already listed is marked "(*)"
+fn generate_tree_content(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+) -> String {
+    let dependency_tree =
+        build_dependency_tree(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        );
+
+    let mut roots = root_packages(imports_map, root_class_prefix);
+    roots.sort();
+
+    let mut tree_content = String::new();
+    let mut expanded = HashSet::<String>::new();
+    let mut chain = Vec::<String>::new();
+
+    for root in roots {
+        write_tree_lines(&dependency_tree, &root, 0, &mut chain, &mut expanded, &mut tree_content);
+    }
+
+    tree_content
+}
+
+// Function to append a package and, recursively, the packages it imports
+fn write_tree_lines(
+    dependency_tree: &DashMap<String, Vec<String>>,
+    package_name: &str,
+    indent: usize,
+    chain: &mut Vec<String>,
+    expanded: &mut HashSet<String>,
+    tree_content: &mut String,
+) {
+    let mut imports = dependency_tree
+        .get(package_name)
+        .map(|imports| imports.value().clone())
+        .unwrap_or_default();
+    imports.sort();
+
+    let marker =
+        if chain.iter().any(|ancestor| ancestor == package_name) {
+            " (cycle)"
+        } else if !imports.is_empty() && expanded.contains(package_name) {
+            " (*)"
+        } else {
+            ""
+        };
+
+    *tree_content += &format!("{}{}{}\n", "  ".repeat(indent), package_name, marker);
+
+    if !marker.is_empty() || !expanded.insert(package_name.to_string()) {
+        return;
+    }
+
+    chain.push(package_name.to_string());
+
+    for import_value in &imports {
+        write_tree_lines(dependency_tree, import_value, indent + 1, chain, expanded, tree_content);
+    }
+
+    chain.pop();
+}
+
+// Function to escape text for use in xml attribute values and elements
+fn escape_xml(
+    text: &str,
+) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// Function to generate a graphml document of the same dependency tree the dot
+// output is built from, using package names as node ids and labels
+fn generate_graphml_content(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+) -> String {
+    let mut graphml_content = String::new();
+    graphml_content += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    graphml_content += "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n";
+    graphml_content += "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n";
+    graphml_content += "  <graph id=\"G\" edgedefault=\"directed\">\n";
+
+    let dependency_tree =
+        build_dependency_tree(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        );
+
+    let mut package_names = BTreeSet::new();
+
+    for entry in dependency_tree.iter() {
+        package_names.insert(entry.key().to_string());
+        package_names.extend(entry.value().iter().cloned());
+    }
+
+    for package_name in &package_names {
+        let package_name = escape_xml(package_name);
+
+        graphml_content += &format!(
+            "    <node id=\"{}\"><data key=\"label\">{}</data></node>\n",
+            package_name,
+            package_name
+        );
+    }
+
+    for (edge_id, (package_name, import_value)) in sorted_edges(dependency_tree).into_iter().enumerate() {
+        graphml_content += &format!(
+            "    <edge id=\"e{}\" source=\"{}\" target=\"{}\"/>\n",
+            edge_id,
+            escape_xml(&package_name),
+            escape_xml(&import_value)
+        );
+    }
+
+    graphml_content += "  </graph>\n";
+    graphml_content += "</graphml>\n";
+
+    graphml_content
+}
+
+// Script of the html output: clicking a node highlights it and its direct neighbors,
+// clicking the background clears that, and the prefix field hides the nodes whose
+// package doesn't start with it, along with their edges
+const HTML_SCRIPT: &str = r#"
+const packages = JSON.parse(document.getElementById("graph-data").textContent);
+const neighbors = new Map();
+const link = (from, to) => {
+  if (!neighbors.has(from)) neighbors.set(from, new Set());
+  neighbors.get(from).add(to);
+};
+for (const { package: name, imports } of packages) {
+  for (const target of imports) {
+    link(name, target);
+    link(target, name);
+  }
+}
+const title = (element) => element.querySelector("title").textContent;
+const nodes = [...document.querySelectorAll("svg g.node")];
+const edges = [...document.querySelectorAll("svg g.edge")];
+const highlight = (name) => {
+  const kept = new Set([name, ...(neighbors.get(name) || [])]);
+  for (const node of nodes) {
+    node.style.opacity = name === null || kept.has(title(node)) ? "" : "0.15";
+  }
+  for (const edge of edges) {
+    const [from, to] = title(edge).split("->");
+    edge.style.opacity = name === null || from === name || to === name ? "" : "0.15";
+  }
+};
+for (const node of nodes) {
+  node.style.cursor = "pointer";
+  node.addEventListener("click", (event) => {
+    event.stopPropagation();
+    highlight(title(node));
+  });
+}
+document.querySelector("svg").addEventListener("click", () => highlight(null));
+document.getElementById("prefix").addEventListener("input", (event) => {
+  const prefix = event.target.value;
+  for (const node of nodes) {
+    node.style.display = title(node).startsWith(prefix) ? "" : "none";
+  }
+  for (const edge of edges) {
+    const [from, to] = title(edge).split("->");
+    edge.style.display = from.startsWith(prefix) && to.startsWith(prefix) ? "" : "none";
+  }
+});
+"#;
+
+// Function to list the packages of a dependency tree as the json subcommand exports
+// them, sorted, marking the nodes that were only imported rather than scanned
+pub fn export_package_imports(
+    imports_map: &DashMap<String, Vec<String>>,
+    dependency_tree: DashMap<String, Vec<String>>,
+) -> Vec<PackageImports> {
+    let mut packages: Vec<PackageImports> = dependency_tree
+        .into_iter()
+        .map(|(package, imports)| {
+            let external = !imports_map.contains_key(&package);
+
+            PackageImports { package, imports, external }
+        })
+        .collect();
+    packages.sort_by(|a, b| a.package.cmp(&b.package));
+
+    packages
+}
+
+// Function to generate a standalone html page around the svg rendered by graphviz,
+// embedding the dependency tree as the json export for the page's script
+pub fn generate_html_content(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+    svg_content: &str,
+) -> anyhow::Result<String> {
+    let dependency_tree =
+        build_dependency_tree(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        );
+
+    let packages = export_package_imports(imports_map, dependency_tree);
+
+    // "</" would end the script element early; "<\/" reads the same in json
+    let graph_data = serde_json::to_string(&packages)?.replace("</", "<\\/");
+
+    // the xml prolog and doctype graphviz writes are not allowed inside html
+    let svg_start = svg_content.find("<svg").unwrap_or(0);
+
+    let mut html_content = String::new();
+    html_content += "<!DOCTYPE html>\n";
+    html_content += "<html>\n<head>\n<meta charset=\"utf-8\">\n";
+    html_content += &format!("<title>{}</title>\n", escape_xml(root_class_prefix.unwrap_or("Dependency graph")));
+    html_content += "<style>body { margin: 0; background: #222; } #filter { position: fixed; top: 8px; left: 8px; }</style>\n";
+    html_content += "</head>\n<body>\n";
+    html_content += "<div id=\"filter\"><input id=\"prefix\" type=\"search\" placeholder=\"Package prefix\"></div>\n";
+    html_content += &svg_content[svg_start..];
+    html_content += "\n";
+    html_content += &format!("<script id=\"graph-data\" type=\"application/json\">{}</script>\n", graph_data);
+    html_content += &format!("<script>{}</script>\n", HTML_SCRIPT);
+    html_content += "</body>\n</html>\n";
+
+    Ok(html_content)
+}
+
+// Function to generate a mermaid flowchart of the same dependency tree the dot
+// output is built from, keeping the full package name as each node's label
+fn generate_mermaid_content(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+    rank_dir: RankDir,
+) -> String {
+    let mut mermaid_content = String::new();
+
+    match rank_dir {
+        RankDir::LR => mermaid_content += "graph LR\n",
+        RankDir::RL => mermaid_content += "graph RL\n",
+        RankDir::TB => mermaid_content += "graph TB\n",
+        RankDir::BT => mermaid_content += "graph BT\n",
+    }
+
+    let dependency_tree =
+        build_dependency_tree(
+            imports_map,
+            root_class_prefix,
+            tree_options,
+        );
+
+    let mut package_names = BTreeSet::new();
+
+    for entry in dependency_tree.iter() {
+        package_names.insert(entry.key().to_string());
+        package_names.extend(entry.value().iter().cloned());
+    }
+
+    // nodes are numbered, since no escaping of package names into mermaid
+    // identifiers keeps e.g. com.a.b and com.a_b apart; the label shows the name
+    let node_ids: HashMap<&String, String> = package_names
+        .iter()
+        .enumerate()
+        .map(|(index, package_name)| (package_name, format!("n{}", index)))
+        .collect();
+
+    for package_name in &package_names {
+        mermaid_content += &format!(
+            "  {}[\"{}\"]\n",
+            node_ids[package_name],
+            package_name
+                .replace('"', "#quot;")
+                .replace('<', "#lt;")
+                .replace('>', "#gt;"),
+        );
+    }
+
+    for (package_name, import_value) in sorted_edges(dependency_tree) {
+        mermaid_content += &format!(
+            "  {} --> {}\n",
+            node_ids[&package_name],
+            node_ids[&import_value],
+        );
+    }
+
+    mermaid_content
+}
+
+// A package and its imports, as exported by the json subcommand; imported classes
+// and packages outside the scanned ones are exported as external nodes without
+// imports, and exports written before they were marked read as all scanned
+#[derive(Serialize, Deserialize)]
+pub struct PackageImports {
+    pub package: String,
+    pub imports: Vec<String>,
+    #[serde(default)]
+    pub external: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum RankDir {
+    #[serde(rename = "lr")]
+    LR,
+    #[serde(rename = "rl")]
+    RL,
+    #[serde(rename = "tb")]
+    TB,
+    #[serde(rename = "bt")]
+    BT,
+}
+
+impl FromStr for RankDir {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "lr" => Ok(RankDir::LR),
+            "rl" => Ok(RankDir::RL),
+            "tb" => Ok(RankDir::TB),
+            "bt" => Ok(RankDir::BT),
+            _ => Err(()),
+        }
+    }
+}
+
+// Presentation options of the rendered graph
+#[derive(Debug, Clone)]
+pub struct GraphStyle {
+    pub rank_dir: RankDir,
+    pub color_nodes: bool,
+    pub cluster_depth: Option<usize>,
+    pub bg_color: String,
+    pub edge_color: String,
+    pub node_color: Option<String>,
+    pub extra_graph_attributes: Vec<(String, String)>,
+    pub extra_node_attributes: Vec<(String, String)>,
+    pub extra_edge_attributes: Vec<(String, String)>,
+}
+
+impl Default for GraphStyle {
+    fn default() -> Self {
+        GraphStyle {
+            rank_dir: RankDir::LR,
+            color_nodes: true,
+            cluster_depth: None,
+            bg_color: "black".to_string(),
+            edge_color: "white".to_string(),
+            node_color: None,
+            extra_graph_attributes: Vec::new(),
+            extra_node_attributes: Vec::new(),
+            extra_edge_attributes: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GraphFormat {
+    #[serde(rename = "dot")]
+    Dot,
+    #[serde(rename = "mermaid")]
+    Mermaid,
+    #[serde(rename = "graphml")]
+    Graphml,
+    #[serde(rename = "tree")]
+    Tree,
+    #[serde(rename = "csv")]
+    Csv,
+    #[serde(rename = "html")]
+    Html,
+}
+
+impl FromStr for GraphFormat {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            "graphml" => Ok(GraphFormat::Graphml),
+            "tree" => Ok(GraphFormat::Tree),
+            "csv" => Ok(GraphFormat::Csv),
+            "html" => Ok(GraphFormat::Html),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::imports_map;
+
+    #[test]
+    fn mermaid_nodes_keep_names_apart_that_differ_in_punctuation() {
+        let imports_map = DashMap::new();
+        imports_map.insert("com.a.b".to_string(), vec!["com.a_b".to_string()]);
+
+        let mermaid_content = generate_mermaid_content(&imports_map, None, &TreeOptions::default(), RankDir::LR);
+
+        assert_eq!(
+            mermaid_content,
+            "graph LR\n  n0[\"com.a.b\"]\n  n1[\"com.a_b\"]\n  n0 --> n1\n",
+        );
+    }
+
+    #[test]
+    fn mermaid_output_links_known_nodes() {
+        let imports_map = imports_map(&[("com.acme.app", &["com.acme.core"]), ("com.acme.core", &[])]);
+
+        let mermaid_content = generate_mermaid_content(&imports_map, None, &TreeOptions::default(), RankDir::LR);
+
+        assert!(mermaid_content.starts_with("graph LR\n"));
+        assert!(mermaid_content.contains("n0[\"com.acme.app\"]"));
+        assert!(mermaid_content.contains("n1[\"com.acme.core\"]"));
+        assert!(mermaid_content.contains("n0 --> n1"));
+    }
+
+    // Function to generate the dot content of a whole imports map in a style
+    fn dot_content(
+        imports_map: &DashMap<String, Vec<String>>,
+        graph_style: &GraphStyle,
+    ) -> String {
+        generate_dot_content(imports_map, None, &TreeOptions::default(), &HashMap::new(), &HashMap::new(), graph_style)
+    }
+
+    #[test]
+    fn clustered_nodes_are_declared_in_subgraphs_with_edges_at_the_top_level() {
+        let imports_map = imports_map(&[
+            ("com.acme.app", &["com.acme.core", "org.lib.Util"]),
+            ("com.acme.core", &[]),
+        ]);
+
+        let dot_content = dot_content(&imports_map, &GraphStyle { cluster_depth: Some(2), ..GraphStyle::default() });
+
+        let acme_cluster = dot_content.find("label=\"com.acme\"").unwrap();
+        let last_cluster_end = dot_content.rfind("\n  }\n").unwrap();
+
+        assert!(dot_content.contains("subgraph cluster_0 {"));
+        assert!(dot_content.contains("subgraph cluster_1 {"));
+        assert!(dot_content.find("    \"com.acme.core\"").unwrap() > acme_cluster);
+        assert!(dot_content.find("  \"com.acme.app\" -> \"org.lib.Util\";").unwrap() > last_cluster_end);
+    }
+
+    #[test]
+    fn dot_output_is_identical_across_runs() {
+        let packages: Vec<(String, Vec<String>)> = (0..50)
+            .map(|index| (format!("p{}", index), vec![format!("p{}", (index * 7) % 50), format!("q{}", index)]))
+            .collect();
+
+        // every map hashes with its own random state, so each iterates in its own order
+        let forward: DashMap<String, Vec<String>> = packages.iter().cloned().collect();
+        let backward: DashMap<String, Vec<String>> = packages.iter().rev().cloned().collect();
+
+        let first_run = dot_content(&forward, &GraphStyle::default());
+
+        assert_eq!(first_run, dot_content(&backward, &GraphStyle::default()));
+        assert_eq!(first_run, dot_content(&forward, &GraphStyle::default()));
+    }
+
+    #[test]
+    fn graphml_output_is_balanced_xml_with_one_node_per_package() {
+        let imports_map = imports_map(&[("com.acme.app", &["com.acme.core", "org.lib<T>"]), ("com.acme.core", &[])]);
+
+        let graphml_content = generate_graphml_content(&imports_map, None, &TreeOptions::default());
+
+        let mut open_elements = Vec::new();
+        for tag in graphml_content.split('<').skip(1).map(|rest| &rest[..rest.find('>').unwrap()]) {
+            if tag.starts_with('?') || tag.ends_with('/') {
+                continue;
+            }
+            match tag.strip_prefix('/') {
+                Some(closed) => assert_eq!(open_elements.pop(), Some(closed.to_string())),
+                None => open_elements.push(tag.split(' ').next().unwrap().to_string()),
+            }
+        }
+        assert!(open_elements.is_empty());
+        assert_eq!(graphml_content.matches("<node ").count(), 3);
+        assert_eq!(graphml_content.matches("<edge ").count(), 2);
+        assert!(graphml_content.contains("target=\"org.lib&lt;T&gt;\""));
+    }
+
+    #[test]
+    fn custom_colors_replace_the_default_scheme() {
+        let imports_map = imports_map(&[("com.acme.app", &["com.acme.core"]), ("com.acme.core", &[])]);
+        let graph_style = GraphStyle {
+            bg_color: "white".to_string(),
+            edge_color: "#333333".to_string(),
+            node_color: Some("no-such-color".to_string()),
+            ..GraphStyle::default()
+        };
+
+        let dot_content = dot_content(&imports_map, &graph_style);
+
+        assert!(dot_content.contains("  graph [bgcolor=\"white\"];\n"));
+        assert!(dot_content.contains("  edge [color=\"#333333\", fontcolor=\"#333333\"];\n"));
+        assert!(dot_content.contains("fillcolor=\"no-such-color\""));
+        assert!(!dot_content.contains("black"));
+    }
+
+    #[test]
+    fn tree_text_indents_imports_and_stops_at_cycles() {
+        let imports_map = imports_map(&[
+            ("com.a", &["com.b", "com.c"]),
+            ("com.b", &["com.c"]),
+            ("com.c", &["com.a"]),
+        ]);
+
+        let tree_content = generate_tree_content(&imports_map, Some("com.a"), &TreeOptions::default());
+
+        assert_eq!(
+            tree_content,
+            "com.a\n  com.b\n    com.c\n      com.a (cycle)\n  com.c (*)\n",
+        );
+    }
+
+    #[test]
+    fn node_tooltips_list_the_files_of_their_package() {
+        let imports_map = imports_map(&[("com.acme.app", &["com.acme.core"]), ("com.acme.core", &[])]);
+        let node_attributes = HashMap::from([(
+            "com.acme.app".to_string(),
+            vec![files_tooltip(&["src/com/acme/app/App.java".to_string(), "src/com/acme/app/Main.java".to_string()])],
+        )]);
+
+        let dot_content =
+            generate_dot_content(&imports_map, None, &TreeOptions::default(), &node_attributes, &HashMap::new(), &GraphStyle::default());
+
+        let app_node = dot_content
+            .lines()
+            .find(|line| line.trim_start().starts_with("\"com.acme.app\" ["))
+            .unwrap();
+
+        assert!(
+            app_node.contains("tooltip=\"2 files\\nsrc/com/acme/app/App.java\\nsrc/com/acme/app/Main.java\""),
+            "{}",
+            app_node,
+        );
+    }
+
+    #[test]
+    fn quotes_and_backslashes_are_escaped_in_dot_strings() {
+        assert_eq!(escape_dot_id("com.\"a\\b\nc/d"), "\"com.\\\"a\\\\b\\nc_d\"");
+        assert_eq!(escape_dot_string("black\\"), "\"black\\\\\"");
+
+        let imports_map = imports_map(&[
+            ("com.acme\"] -> \"evil", &["com.lib\\"]),
+            ("com.lib\\", &[]),
+            ("x\"y.app", &[]),
+        ]);
+
+        let graph_style = GraphStyle {
+            cluster_depth: Some(1),
+            bg_color: "black\\".to_string(),
+            edge_color: "white\"]; node [shape=egg".to_string(),
+            node_color: Some("\\".to_string()),
+            ..GraphStyle::default()
+        };
+
+        let dot_content = dot_content(&imports_map, &graph_style);
+
+        // every line holds whole quoted strings: its unescaped quotes pair up
+        for line in dot_content.lines() {
+            let mut quotes = 0;
+            let mut escaped = false;
+
+            for character in line.chars() {
+                match character {
+                    '\\' if !escaped => escaped = true,
+                    '"' if !escaped => quotes += 1,
+                    _ => escaped = false,
+                }
+            }
+
+            assert_eq!(quotes % 2, 0, "{}", line);
+        }
+
+        assert!(dot_content.contains("\"com.acme\\\"] -> \\\"evil\" -> \"com.lib\\\\\""));
+        assert!(dot_content.contains("graph [bgcolor=\"black\\\\\"];"));
+        assert!(dot_content.contains("label=\"x\\\"y\";"));
+    }
+
+    #[test]
+    fn streamed_dot_matches_the_generated_string() {
+        let imports_map = imports_map(&[
+            ("com.acme.app", &["com.acme.core", "org.lib.Util"]),
+            ("com.acme.core", &["org.lib.Util"]),
+        ]);
+        let node_attributes = HashMap::from([("com.acme.core".to_string(), vec!["shape=ellipse".to_string()])]);
+        let graph_style = GraphStyle { cluster_depth: Some(2), ..GraphStyle::default() };
+
+        let dot_path = std::env::temp_dir().join(format!("jadep-streamed-{}.dot", std::process::id()));
+        let mut dot_file = std::io::BufWriter::new(std::fs::File::create(&dot_path).unwrap());
+        write_dot_content(&mut dot_file, &imports_map, None, &TreeOptions::default(), &node_attributes, &HashMap::new(), &graph_style)
+            .unwrap();
+        dot_file.into_inner().unwrap();
+
+        let streamed = std::fs::read(&dot_path).unwrap();
+        std::fs::remove_file(&dot_path).unwrap();
+
+        let generated =
+            generate_dot_content(&imports_map, None, &TreeOptions::default(), &node_attributes, &HashMap::new(), &graph_style);
+
+        assert_eq!(streamed, generated.into_bytes());
+    }
+
+    #[test]
+    fn csv_edge_list_has_a_header_and_one_quoted_row_per_edge() {
+        let imports_map = imports_map(&[
+            ("com.acme.app", &["com.acme.core", "weird,name"]),
+            ("com.acme.core", &["org.lib.\"Util\""]),
+        ]);
+
+        let csv_content = generate_csv_content(&imports_map, None, &TreeOptions::default());
+
+        assert_eq!(
+            csv_content,
+            "source,target\n\
+             com.acme.app,com.acme.core\n\
+             com.acme.app,\"weird,name\"\n\
+             com.acme.core,\"org.lib.\"\"Util\"\"\"\n",
+        );
+    }
+
+    #[test]
+    fn html_page_embeds_the_svg_and_the_graph_data() {
+        let imports_map = imports_map(&[("com.acme.app", &["com.acme.core", "org.lib.</script>"]), ("com.acme.core", &[])]);
+        let svg_content = "<?xml version=\"1.0\"?>\n<!DOCTYPE svg>\n<svg><g class=\"node\"><title>com.acme.app</title></g></svg>\n";
+
+        let html_content = generate_html_content(&imports_map, None, &TreeOptions::default(), svg_content).unwrap();
+
+        let data_start = html_content.find("<script id=\"graph-data\" type=\"application/json\">").unwrap();
+        let data_json = &html_content[data_start..];
+        let data_json = &data_json[data_json.find('>').unwrap() + 1..data_json.find("</script>").unwrap()];
+        let packages: Vec<PackageImports> = serde_json::from_str(data_json).unwrap();
+
+        assert!(html_content.starts_with("<!DOCTYPE html>\n"));
+        assert!(html_content.contains("<svg><g class=\"node\">"));
+        assert!(!html_content.contains("<?xml"));
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[0].package, "com.acme.app");
+        assert_eq!(packages[0].imports, vec!["com.acme.core", "org.lib.</script>"]);
+    }
+
+    // Function to find the attributes of a node declaration in dot content
+    fn node_line<'a>(
+        dot_content: &'a str,
+        package_name: &str,
+    ) -> &'a str {
+        let declaration = format!("\"{}\" [", package_name);
+        dot_content
+            .lines()
+            .find(|line| line.trim_start().starts_with(&declaration))
+            .unwrap()
+    }
+
+    #[test]
+    fn packages_of_one_namespace_share_a_fill_color_unless_coloring_is_off() {
+        let imports_map = imports_map(&[
+            ("com.acme.app.ui", &["com.acme.app.core", "org.lib.util.Strings"]),
+            ("com.acme.app.core", &[]),
+        ]);
+
+        let colored = dot_content(&imports_map, &GraphStyle::default());
+        let fill_color = |package_name: &str| {
+            let line = node_line(&colored, package_name);
+            let start = line.find("fillcolor=").unwrap();
+            line[start..].split(',').next().unwrap().trim_end_matches("];").to_string()
+        };
+
+        assert_eq!(fill_color("com.acme.app.ui"), fill_color("com.acme.app.core"));
+        assert_eq!(fill_color("com.acme.app.ui"), namespace_fill_color("com.acme.app.ui"));
+        assert_ne!(fill_color("com.acme.app.ui"), fill_color("org.lib.util.Strings"));
+
+        let uncolored = dot_content(&imports_map, &GraphStyle { color_nodes: false, ..GraphStyle::default() });
+
+        assert!(!uncolored.contains("fillcolor="), "{}", uncolored);
+    }
+}
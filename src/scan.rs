@@ -0,0 +1,1542 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{bail, Context};
+use dashmap::{DashMap, DashSet};
+use globset::GlobSet;
+use ignore::gitignore::Gitignore;
+use log::{debug, info, warn};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::ParseCache;
+use crate::parse::{
+    decode_source_reporting,
+    extract_import_lines,
+    extract_package_declarations,
+    is_module_descriptor,
+    parse_package_imports,
+    parse_source_text,
+    DEFAULT_LANGUAGES,
+    ImportKind,
+    Language,
+    ParseOptions,
+    SourceFile,
+};
+
+// Extensions of the archives whose source entries are parsed in place
+const ARCHIVE_EXTENSIONS: [&str; 2] = ["jar", "zip"];
+
+// Prefix of the packages of test sources with --separate-tests
+const TEST_PACKAGE_TAG: &str = "[test] ";
+
+// Package of the source files without a package declaration
+pub(crate) const DEFAULT_PACKAGE: &str = "<default>";
+
+// Function to walk a folder with a rayon work queue, parsing every java and kotlin file
+pub fn traverse_folder_par(
+    folder_path: PathBuf,
+    parsed_files: Option<&Sender<ParsedFile>>,
+    walk_filter: &WalkFilter,
+    parse_cache: Option<&ParseCache>,
+    progress: Option<&(dyn Fn() + Sync)>,
+) -> DashMap<String, Vec<String>> {
+    let imports_map: DashMap<String, Vec<String>> = DashMap::new();
+    let started = Instant::now();
+
+    // every traversal keeps track of the folders it has entered on its own
+    let walk_filter = WalkFilter {
+        visited_folders: Arc::default(),
+        ..walk_filter.clone()
+    };
+
+    rayon::scope(|scope| {
+        walk_folder(
+            scope,
+            folder_path.clone(),
+            &imports_map,
+            parsed_files,
+            &walk_filter,
+            parse_cache,
+            progress,
+        );
+    });
+
+    dedup_imports(&imports_map);
+
+    if imports_map.contains_key(DEFAULT_PACKAGE) {
+        info!(
+            "Files without a package declaration below {} are in the {} package",
+            folder_path.display(),
+            DEFAULT_PACKAGE,
+        );
+    }
+
+    debug!(
+        "Scanned {} packages below {} in {:.2?}",
+        imports_map.len(),
+        folder_path.display(),
+        started.elapsed(),
+    );
+
+    imports_map
+}
+
+// Function to queue the subdirectories and source files of a single folder
+fn walk_folder<'scope>(
+    scope: &rayon::Scope<'scope>,
+    folder_path: PathBuf,
+    imports_map: &'scope DashMap<String, Vec<String>>,
+    parsed_files: Option<&'scope Sender<ParsedFile>>,
+    walk_filter: &WalkFilter,
+    parse_cache: Option<&'scope ParseCache>,
+    progress: Option<&'scope (dyn Fn() + Sync)>,
+) {
+    // a symbolic link back to an ancestor would otherwise be walked forever
+    if walk_filter.follow_symlinks {
+        let canonical_path = fs::canonicalize(&folder_path).unwrap_or_else(|_| folder_path.clone());
+
+        if !walk_filter.visited_folders.insert(canonical_path) {
+            debug!("Skipping {}, its folder was already scanned", folder_path.display());
+            return;
+        }
+    }
+
+    let walk_filter = walk_filter.enter(&folder_path);
+
+    debug!("Scanning {}", folder_path.display());
+
+    let entries = match fs::read_dir(&folder_path) {
+        Ok(entries) => entries,
+        Err(error) => {
+            warn!("skipping {}: {}", folder_path.display(), error);
+            return;
+        }
+    };
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                warn!("skipping entry in {}: {}", folder_path.display(), error);
+                continue;
+            }
+        };
+
+        let file_path = entry.path();
+        let is_symlink = entry.file_type().is_ok_and(|file_type| file_type.is_symlink());
+
+        // follows symlinks, so broken links are reported and skipped here
+        let metadata = match fs::metadata(&file_path) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                warn!("skipping {}: {}", file_path.display(), error);
+                continue;
+            }
+        };
+
+        if walk_filter.is_excluded(&file_path, metadata.is_dir()) {
+            continue;
+        }
+
+        if metadata.is_file() {
+            // check if the file is a java or kotlin file
+            if is_source_file(&file_path, &walk_filter.languages) {
+                let separate_tests = walk_filter.separate_tests;
+                let parse_options = walk_filter.parse_options.clone();
+
+                scope.spawn(move |_| {
+                    parse_source_file(&file_path, imports_map, parsed_files, parse_cache, progress, separate_tests, &parse_options)
+                });
+            }
+        } else if metadata.is_dir() {
+            if is_symlink && !walk_filter.follow_symlinks {
+                debug!("Skipping {}, a symbolic link to a folder", file_path.display());
+                continue;
+            }
+
+            let walk_filter = walk_filter.clone();
+
+            scope.spawn(move |scope| {
+                walk_folder(scope, file_path, imports_map, parsed_files, &walk_filter, parse_cache, progress)
+            });
+        }
+    }
+}
+
+// Function to check whether a file is a source file of one of the languages
+fn is_source_file(
+    file_path: &Path,
+    languages: &[Language],
+) -> bool {
+    Language::of_file(file_path).is_some_and(|language| languages.contains(&language))
+}
+
+// Function to parse a single source file into the imports map
+fn parse_source_file(
+    file_path: &Path,
+    imports_map: &DashMap<String, Vec<String>>,
+    parsed_files: Option<&Sender<ParsedFile>>,
+    parse_cache: Option<&ParseCache>,
+    progress: Option<&(dyn Fn() + Sync)>,
+    separate_tests: bool,
+    parse_options: &ParseOptions,
+) {
+    let parsed =
+        match parse_cache {
+            Some(parse_cache) => parse_cache.parse(file_path, parse_options),
+            None => parse_package_imports(file_path, parse_options),
+        };
+
+    add_parsed_file(file_path, parsed, imports_map, parsed_files, progress, separate_tests);
+}
+
+// Function to add the package and imports parsed from a source file to the imports map
+fn add_parsed_file(
+    file_path: &Path,
+    parsed: Option<SourceFile>,
+    imports_map: &DashMap<String, Vec<String>>,
+    parsed_files: Option<&Sender<ParsedFile>>,
+    progress: Option<&(dyn Fn() + Sync)>,
+    separate_tests: bool,
+) {
+    if let Some(source) = parsed {
+        let package_name =
+            if separate_tests && is_test_source(file_path) {
+                format!("{}{}", TEST_PACKAGE_TAG, source.package)
+            } else {
+                source.package.clone()
+            };
+
+        let imports = source.import_targets();
+
+        if let Some(parsed_files) = parsed_files {
+            let _ = parsed_files.send(ParsedFile {
+                file: file_path.display().to_string(),
+                package: package_name.clone(),
+                imports: imports.clone(),
+                source,
+            });
+        }
+
+        // a package spans many files, so merge rather than overwrite
+        imports_map.entry(package_name).or_default().extend(imports);
+    }
+
+    if let Some(progress) = progress {
+        progress();
+    }
+}
+
+// Function to check whether a path is in a test source set, taken to be below a
+// "test" folder directly inside a "src" folder as in maven and gradle projects
+fn is_test_source(
+    path: &Path,
+) -> bool {
+    let components: Vec<_> = path.components().map(|component| component.as_os_str()).collect();
+
+    components.windows(2).any(|pair| pair[0] == "src" && pair[1] == "test")
+}
+
+// Function to check whether a path is a jar or zip archive, e.g. a sources jar
+pub fn is_archive(
+    path: &Path,
+) -> bool {
+    path.is_file() && path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            ARCHIVE_EXTENSIONS
+                .iter()
+                .any(|archive_extension| extension.eq_ignore_ascii_case(archive_extension))
+        })
+}
+
+// Function to parse the java and kotlin entries of a jar or zip archive without
+// extracting it; entries are named as if the archive were a folder
+pub fn parse_archive(
+    archive_path: &Path,
+    parsed_files: Option<&Sender<ParsedFile>>,
+    walk_filter: &WalkFilter,
+    progress: Option<&(dyn Fn() + Sync)>,
+) -> anyhow::Result<DashMap<String, Vec<String>>> {
+    let started = Instant::now();
+
+    let archive_file = fs::File::open(archive_path)
+        .with_context(|| format!("could not open {}", archive_path.display()))?;
+    let mut archive = zip::ZipArchive::new(std::io::BufReader::new(archive_file))
+        .with_context(|| format!("could not read archive {}", archive_path.display()))?;
+
+    // entries can only be read one at a time, so they are parsed once all are read
+    let mut source_entries = Vec::new();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)
+            .with_context(|| format!("could not read archive {}", archive_path.display()))?;
+
+        // names escaping the archive's root are skipped, like any other unusable entry
+        let Some(entry_path) = entry.enclosed_name().map(|entry_name| archive_path.join(entry_name)) else {
+            continue;
+        };
+
+        if !entry.is_file() || !is_source_file(&entry_path, &walk_filter.languages) || walk_filter.is_excluded(&entry_path, false) {
+            continue;
+        }
+
+        let mut entry_content = Vec::new();
+        std::io::Read::read_to_end(&mut entry, &mut entry_content)
+            .with_context(|| format!("could not read {}", entry_path.display()))?;
+
+        source_entries.push((entry_path, entry_content));
+    }
+
+    let imports_map: DashMap<String, Vec<String>> = DashMap::new();
+
+    source_entries.par_iter().for_each(|(entry_path, entry_content)| {
+        let entry_content = decode_source_reporting(entry_path, entry_content, &walk_filter.parse_options);
+        let parsed = parse_source_text(entry_path, &entry_content, &walk_filter.parse_options);
+
+        add_parsed_file(entry_path, parsed, &imports_map, parsed_files, progress, walk_filter.separate_tests);
+    });
+
+    dedup_imports(&imports_map);
+
+    debug!(
+        "Scanned {} packages in {} in {:.2?}",
+        imports_map.len(),
+        archive_path.display(),
+        started.elapsed(),
+    );
+
+    Ok(imports_map)
+}
+
+// Function to sort the imports of every package and drop duplicates
+pub(crate) fn dedup_imports(
+    imports_map: &DashMap<String, Vec<String>>,
+) {
+    imports_map.iter_mut().for_each(|mut entry| {
+        let imports = entry.value_mut();
+        imports.sort();
+        imports.dedup();
+    });
+}
+
+// Function to find the conventional source roots of every module below base_path
+pub fn discover_source_roots(
+    base_path: &Path,
+    layout: Layout,
+) -> Vec<(PathBuf, SourceSet)> {
+    let mut source_roots = Vec::new();
+    let mut stack = vec![base_path.to_path_buf()];
+
+    while let Some(path) = stack.pop() {
+        if let Some(source_set) = layout.source_set_of(&path) {
+            source_roots.push((path, source_set));
+            continue;
+        }
+
+        if let Ok(entries) = fs::read_dir(&path) {
+            for entry in entries.flatten() {
+                if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                    stack.push(entry.path());
+                }
+            }
+        }
+    }
+
+    source_roots.sort();
+
+    source_roots
+}
+
+// Function to read newline-separated file paths from a file, or stdin for "-"
+pub fn read_file_list(
+    source: &str,
+) -> std::io::Result<Vec<PathBuf>> {
+    let content =
+        if source == "-" {
+            std::io::read_to_string(std::io::stdin())?
+        } else {
+            fs::read_to_string(source)?
+        };
+
+    Ok(
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    )
+}
+
+// Function to turn a bazel source file label, e.g. //com/acme/app:App.java, into its
+// path below the workspace; labels of external repositories (@repo//...) have none
+fn bazel_label_path(
+    workspace: &Path,
+    label: &str,
+) -> Option<PathBuf> {
+    let label = label.strip_prefix("//")?;
+
+    let (package_path, target) =
+        match label.split_once(':') {
+            Some((package_path, target)) => (package_path, target),
+            None => (label, label.rsplit('/').next()?),
+        };
+
+    Some(workspace.join(package_path).join(target))
+}
+
+// Function to resolve listed bazel labels to source file paths below the workspace,
+// keeping plain paths as they are
+pub fn resolve_bazel_labels(
+    workspace: &Path,
+    entries: &[String],
+) -> Vec<PathBuf> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            if entry.starts_with("//") {
+                bazel_label_path(workspace, entry)
+            } else if entry.starts_with('@') {
+                debug!("Skipping external label {}", entry);
+                None
+            } else {
+                Some(PathBuf::from(entry))
+            }
+        })
+        .collect()
+}
+
+// Function to list the source file labels of the java and kotlin libraries matching
+// a bazel target pattern, by running bazel query in the workspace
+pub fn query_bazel_sources(
+    workspace: &Path,
+    target_pattern: &str,
+) -> anyhow::Result<Vec<String>> {
+    let query = format!("labels(srcs, kind(\"(java|kt_jvm)_library\", {}))", target_pattern);
+
+    info!("Running bazel query {}", query);
+
+    let output = Command::new("bazel")
+        .arg("query")
+        .arg(&query)
+        .arg("--output=label")
+        .current_dir(workspace)
+        .stderr(Stdio::inherit())
+        .output()
+        .context("could not run bazel; is it installed and on the PATH?")?;
+
+    if !output.status.success() {
+        bail!("bazel query failed with {}", output.status);
+    }
+
+    Ok(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect()
+    )
+}
+
+// Function to parse the listed source files into an imports map without walking
+// any folder; files that are missing, excluded or not java/kotlin are skipped
+pub fn parse_listed_files(
+    listed_files: &[PathBuf],
+    parsed_files: Option<&Sender<ParsedFile>>,
+    walk_filter: &WalkFilter,
+    parse_cache: Option<&ParseCache>,
+    progress: Option<&(dyn Fn() + Sync)>,
+) -> DashMap<String, Vec<String>> {
+    let imports_map: DashMap<String, Vec<String>> = DashMap::new();
+
+    listed_files.par_iter().for_each(|file_path| {
+        if !is_source_file(file_path, &walk_filter.languages) || walk_filter.is_excluded(file_path, false) {
+            return;
+        }
+
+        if !file_path.is_file() {
+            warn!("skipping {}: not a file", file_path.display());
+            return;
+        }
+
+        parse_source_file(
+            file_path,
+            &imports_map,
+            parsed_files,
+            parse_cache,
+            progress,
+            walk_filter.separate_tests,
+            &walk_filter.parse_options,
+        );
+    });
+
+    dedup_imports(&imports_map);
+
+    if imports_map.contains_key(DEFAULT_PACKAGE) {
+        info!("Listed files without a package declaration are in the {} package", DEFAULT_PACKAGE);
+    }
+
+    imports_map
+}
+
+// Function to merge the packages of one imports map into another
+pub fn merge_imports_maps(
+    imports_map: &DashMap<String, Vec<String>>,
+    other: DashMap<String, Vec<String>>,
+) {
+    for (package_name, imports) in other {
+        imports_map.entry(package_name).or_default().extend(imports);
+    }
+
+    dedup_imports(imports_map);
+}
+
+// Function to collect the source files below a folder that the graph walk would
+// parse, sorted by path; the walk filter's excludes, .gitignore rules, languages and
+// symbolic link policy apply just like they do there
+pub fn collect_source_files(
+    folder_path: &Path,
+    walk_filter: &WalkFilter,
+) -> Vec<PathBuf> {
+    let mut source_files = Vec::new();
+    let visited_folders = DashSet::new();
+    let mut stack = vec![(folder_path.to_path_buf(), walk_filter.clone())];
+
+    while let Some((folder_path, parent_filter)) = stack.pop() {
+        // a symbolic link back to an ancestor would otherwise be walked forever
+        if parent_filter.follow_symlinks {
+            let canonical_path = fs::canonicalize(&folder_path).unwrap_or_else(|_| folder_path.clone());
+
+            if !visited_folders.insert(canonical_path) {
+                continue;
+            }
+        }
+
+        let walk_filter = parent_filter.enter(&folder_path);
+
+        let Ok(entries) = fs::read_dir(&folder_path) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let file_path = entry.path();
+            let is_symlink = entry.file_type().is_ok_and(|file_type| file_type.is_symlink());
+
+            let Ok(metadata) = fs::metadata(&file_path) else {
+                continue;
+            };
+
+            if walk_filter.is_excluded(&file_path, metadata.is_dir()) {
+                continue;
+            }
+
+            if metadata.is_file() && is_source_file(&file_path, &walk_filter.languages) {
+                source_files.push(file_path);
+            } else if metadata.is_dir() && (!is_symlink || walk_filter.follow_symlinks) {
+                stack.push((file_path, walk_filter.clone()));
+            }
+        }
+    }
+
+    source_files.sort();
+
+    source_files
+}
+
+// Function to find all wildcard imports ("import [static] <some.value>.*;") with their line numbers
+pub fn find_wildcard_imports(
+    file_path: &Path,
+    parse_options: &ParseOptions,
+) -> Vec<(usize, String)> {
+    extract_import_lines(file_path, parse_options)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(_, import)| import.target.ends_with(".*"))
+        .map(|(line_number, import)| (line_number, import.target))
+        .collect()
+}
+
+// Function to find the java files whose package declaration doesn't match their
+// folder below the source root, as (file, declared package, implied package); kotlin
+// files may leave out a common package prefix from their folders, so only java files
+// are checked, and files outside the source root are skipped
+pub fn find_package_mismatches(
+    folder_path: &Path,
+    source_root: &Path,
+    walk_filter: &WalkFilter,
+) -> Vec<(PathBuf, String, String)> {
+    let mut mismatches = Vec::new();
+
+    for file_path in collect_source_files(folder_path, walk_filter) {
+        if Language::of_file(&file_path) != Some(Language::Java) || is_module_descriptor(&file_path) {
+            continue;
+        }
+
+        let Some(folder) = file_path.parent().and_then(|parent| parent.strip_prefix(source_root).ok()) else {
+            continue;
+        };
+
+        let implied_package = folder
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(".");
+
+        let declared_package = extract_package_declarations(&file_path, &walk_filter.parse_options)
+            .and_then(|package_names| package_names.into_iter().next())
+            .unwrap_or_default();
+
+        if declared_package != implied_package {
+            let package_or_default = |package_name: String| {
+                if package_name.is_empty() { DEFAULT_PACKAGE.to_string() } else { package_name }
+            };
+
+            mismatches.push((
+                file_path,
+                package_or_default(declared_package),
+                package_or_default(implied_package),
+            ));
+        }
+    }
+
+    mismatches
+}
+
+// Function to collect, for every (package, import) edge, the files and lines of
+// the import statements that create it from the files parsed by the walk, with
+// paths relative to the scan root they were found below
+pub fn collect_edge_provenance(
+    parsed_files: &[ParsedFile],
+    scan_roots: &[PathBuf],
+) -> Vec<EdgeProvenance> {
+    let mut edges = BTreeMap::<(String, String), Vec<ImportSite>>::new();
+
+    for parsed_file in parsed_files {
+        let file_path = Path::new(&parsed_file.file);
+
+        let file = scan_roots
+            .iter()
+            .find_map(|scan_root| file_path.strip_prefix(scan_root).ok())
+            .unwrap_or(file_path)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        for (line, import) in &parsed_file.source.imports {
+            edges
+                .entry((parsed_file.package.clone(), import.target.clone()))
+                .or_default()
+                .push(ImportSite { file: file.clone(), line: *line });
+        }
+    }
+
+    edges
+        .into_iter()
+        .map(|((source, target), mut occurrences)| {
+            occurrences.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+
+            EdgeProvenance { source, target, occurrences }
+        })
+        .collect()
+}
+
+// Function to resolve the wildcard imports of the files parsed by the walk against
+// the classes the scanned packages declare, in two passes: first index the declared
+// classes of every package, then replace each wildcard import with the classes the
+// importing files use that exactly one of their wildcard packages declares.
+// Returns the (file, class, candidate packages) that were left ambiguous.
+pub fn resolve_wildcard_imports(
+    parsed_files: &[ParsedFile],
+    imports_map: &DashMap<String, Vec<String>>,
+) -> Vec<(String, String, Vec<String>)> {
+    // imports name packages as declared, so test sources tagged by --separate-tests
+    // are indexed under their declared package as well
+    let mut declared_classes = HashMap::<&str, HashSet<&str>>::new();
+
+    for parsed_file in parsed_files {
+        declared_classes
+            .entry(parsed_file.source.package.as_str())
+            .or_default()
+            .extend(parsed_file.source.declared_classes.iter().map(String::as_str));
+    }
+
+    // per package: resolved class imports, wildcards they replace, wildcards still needed
+    let resolved = DashMap::<String, (HashSet<String>, HashSet<String>, HashSet<String>)>::new();
+    let ambiguities = DashSet::<(String, String, Vec<String>)>::new();
+
+    parsed_files.par_iter().for_each(|parsed_file| {
+        let source = &parsed_file.source;
+
+        let wildcard_packages: Vec<&str> = source
+            .imports
+            .iter()
+            .filter(|(_, import)| import.kind == ImportKind::Wildcard)
+            .filter_map(|(_, import)| import.target.strip_suffix(".*"))
+            .filter(|wildcard_package| declared_classes.contains_key(*wildcard_package))
+            .collect();
+
+        if wildcard_packages.is_empty() {
+            return;
+        }
+
+        // single-type imports and classes of the file's own package shadow wildcard imports
+        let mut shadowed: HashSet<&str> = source
+            .imports
+            .iter()
+            .filter(|(_, import)| import.kind == ImportKind::Normal)
+            .filter_map(|(_, import)| import.target.rsplit('.').next())
+            .collect();
+
+        if let Some(own_classes) = declared_classes.get(source.package.as_str()) {
+            shadowed.extend(own_classes);
+        }
+
+        let mut entry = resolved.entry(parsed_file.package.clone()).or_default();
+
+        for class_name in &source.used_classes {
+            if shadowed.contains(class_name.as_str()) {
+                continue;
+            }
+
+            let candidates: Vec<String> = wildcard_packages
+                .iter()
+                .filter(|wildcard_package| {
+                    declared_classes
+                        .get(**wildcard_package)
+                        .is_some_and(|classes| classes.contains(class_name.as_str()))
+                })
+                .map(|wildcard_package| wildcard_package.to_string())
+                .collect();
+
+            match candidates.as_slice() {
+                [] => {}
+                [wildcard_package] => {
+                    entry.0.insert(format!("{}.{}", wildcard_package, class_name));
+                    entry.1.insert(format!("{}.*", wildcard_package));
+                }
+                _ => {
+                    let mut candidates = candidates;
+                    candidates.sort();
+
+                    for wildcard_package in &candidates {
+                        entry.2.insert(format!("{}.*", wildcard_package));
+                    }
+
+                    ambiguities.insert((parsed_file.file.clone(), class_name.clone(), candidates));
+                }
+            }
+        }
+    });
+
+    for (package_name, (resolved_imports, resolved_wildcards, ambiguous_wildcards)) in resolved {
+        if let Some(mut imports) = imports_map.get_mut(&package_name) {
+            imports.retain(|import_value| {
+                !resolved_wildcards.contains(import_value)
+                    || ambiguous_wildcards.contains(import_value)
+            });
+
+            let mut resolved_imports: Vec<String> = resolved_imports
+                .into_iter()
+                .filter(|import_value| !imports.contains(import_value))
+                .collect();
+            resolved_imports.sort();
+
+            imports.extend(resolved_imports);
+        }
+    }
+
+    let mut ambiguities: Vec<(String, String, Vec<String>)> = ambiguities.into_iter().collect();
+    ambiguities.sort();
+
+    ambiguities
+}
+
+// Function to count, per graph node, the commits since the given date that touched
+// one of the files parsed by the walk (e.g. "2 weeks ago" or "2024-01-01", as
+// understood by git); `node_name` maps a package to the node it is drawn as, and
+// a commit is counted once per node even when it touches several of its packages
+// or scanned folders
+pub fn count_package_churn(
+    parsed_files: &[ParsedFile],
+    scan_roots: &[PathBuf],
+    since: &str,
+    node_name: impl Fn(&str) -> String,
+) -> HashMap<String, usize> {
+    // git names files relative to the folder it runs in, so both sides are canonicalized
+    let canonical_path = |file_path: &Path| fs::canonicalize(file_path).unwrap_or_else(|_| file_path.to_path_buf());
+
+    let file_packages: HashMap<PathBuf, String> = parsed_files
+        .iter()
+        .map(|parsed_file| (canonical_path(Path::new(&parsed_file.file)), node_name(&parsed_file.package)))
+        .collect();
+
+    let mut commit_packages = HashMap::<String, HashSet<&str>>::new();
+
+    for scan_root in scan_roots.iter().filter(|scan_root| !is_archive(scan_root)) {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(scan_root)
+            .args(["log", "--relative", "--name-only", "--pretty=format:commit %H"])
+            .arg(format!("--since={}", since))
+            .arg("--")
+            .arg(".")
+            .output();
+
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                warn!(
+                    "git log failed in {}: {}",
+                    scan_root.display(),
+                    String::from_utf8_lossy(&output.stderr).trim(),
+                );
+                continue;
+            }
+            Err(error) => {
+                warn!("could not run git: {}", error);
+                continue;
+            }
+        };
+
+        let mut commit = String::new();
+
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if let Some(hash) = line.strip_prefix("commit ") {
+                commit = hash.to_string();
+            } else if let Some(package_name) = file_packages.get(&canonical_path(&scan_root.join(line))) {
+                commit_packages.entry(commit.clone()).or_default().insert(package_name);
+            }
+        }
+    }
+
+    let mut churn = HashMap::<String, usize>::new();
+
+    for package_name in commit_packages.into_values().flatten() {
+        *churn.entry(package_name.to_string()).or_default() += 1;
+    }
+
+    churn
+}
+
+// Paths to leave out when walking a folder: --exclude globs, module-info.java
+// files unless --modules is given, test sources with --exclude-tests and,
+// optionally, the rules of the .gitignore files found along the way; with
+// --separate-tests the packages of test sources are tagged instead. It also holds
+// how the files are parsed and whether symbolic links to folders are followed
+#[derive(Clone)]
+pub struct WalkFilter {
+    pub exclude: Arc<GlobSet>,
+    pub respect_gitignore: bool,
+    pub gitignores: Vec<Arc<Gitignore>>,
+    pub modules: bool,
+    pub exclude_tests: bool,
+    pub separate_tests: bool,
+    pub languages: Vec<Language>,
+    pub parse_options: ParseOptions,
+    pub follow_symlinks: bool,
+    pub visited_folders: Arc<DashSet<PathBuf>>,
+}
+
+impl Default for WalkFilter {
+    fn default() -> Self {
+        WalkFilter {
+            exclude: Arc::default(),
+            respect_gitignore: false,
+            gitignores: Vec::new(),
+            modules: false,
+            exclude_tests: false,
+            separate_tests: false,
+            languages: DEFAULT_LANGUAGES.to_vec(),
+            parse_options: ParseOptions::default(),
+            follow_symlinks: false,
+            visited_folders: Arc::default(),
+        }
+    }
+}
+
+impl WalkFilter {
+    // Function to describe every setting the parsed package and imports of a file
+    // depend on, so that cached files parsed with other settings aren't reused
+    pub fn fingerprint(&self) -> String {
+        let parse_options = &self.parse_options;
+
+        format!(
+            "encoding={:?} package-regex={:?} import-regex={:?} lang={:?} modules={}",
+            parse_options.encoding,
+            parse_options.package_regex.as_ref().map(regex::Regex::as_str),
+            parse_options.import_regex.as_ref().map(regex::Regex::as_str),
+            self.languages,
+            self.modules,
+        )
+    }
+
+    // Function to get the filter for the entries of a folder, picking up its .gitignore
+    pub fn enter(&self, folder_path: &Path) -> WalkFilter {
+        let mut walk_filter = self.clone();
+        let gitignore_path = folder_path.join(".gitignore");
+
+        if self.respect_gitignore && gitignore_path.is_file() {
+            let (gitignore, error) = Gitignore::new(&gitignore_path);
+
+            if let Some(error) = error {
+                warn!("{}: {}", gitignore_path.display(), error);
+            }
+
+            walk_filter.gitignores.push(Arc::new(gitignore));
+        }
+
+        walk_filter
+    }
+
+    // Function to check whether a path is excluded; nested .gitignore files take precedence
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        // a trailing slash lets directory globs like "**/build/**" prune the directory itself
+        let excluded = if is_dir {
+            self.exclude.is_match(path) || self.exclude.is_match(format!("{}/", path.display()))
+        } else {
+            self.exclude.is_match(path) || (!self.modules && is_module_descriptor(path))
+        };
+
+        excluded || (self.exclude_tests && is_test_source(path)) || self
+            .gitignores
+            .iter()
+            .rev()
+            .find_map(|gitignore| match gitignore.matched(path, is_dir) {
+                ignore::Match::None => None,
+                ignore::Match::Ignore(_) => Some(true),
+                ignore::Match::Whitelist(_) => Some(false),
+            })
+            .unwrap_or(false)
+    }
+}
+
+// A file as parsed by the walk, with the package its imports are counted in, which
+// --separate-tests may have tagged; --stream-parse writes all but its source
+#[derive(Serialize)]
+pub struct ParsedFile {
+    pub file: String,
+    pub package: String,
+    pub imports: Vec<String>,
+    #[serde(skip)]
+    pub source: SourceFile,
+}
+
+#[derive(Serialize)]
+pub struct ImportSite {
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Serialize)]
+pub struct EdgeProvenance {
+    pub source: String,
+    pub target: String,
+    pub occurrences: Vec<ImportSite>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SourceSet {
+    Main,
+    Test,
+}
+
+impl SourceSet {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceSet::Main => "main",
+            SourceSet::Test => "test",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum Layout {
+    #[serde(rename = "maven")]
+    Maven,
+    #[serde(rename = "gradle")]
+    Gradle,
+}
+
+impl Layout {
+    // Maven and Gradle share the same conventional java source roots
+    pub fn source_set_of(&self, path: &Path) -> Option<SourceSet> {
+        match self {
+            Layout::Maven | Layout::Gradle => {
+                if path.ends_with("src/main/java") {
+                    Some(SourceSet::Main)
+                } else if path.ends_with("src/test/java") {
+                    Some(SourceSet::Test)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum BuildSystem {
+    #[serde(rename = "bazel")]
+    Bazel,
+}
+
+impl FromStr for BuildSystem {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bazel" => Ok(BuildSystem::Bazel),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "maven" => Ok(Layout::Maven),
+            "gradle" => Ok(Layout::Gradle),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{fixture, git};
+    use crate::transform::package_prefix;
+    use globset::{Glob, GlobSetBuilder};
+    use std::io::Write;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // Function to list the packages of an imports map, sorted
+    fn package_names(
+        imports_map: &DashMap<String, Vec<String>>,
+    ) -> Vec<String> {
+        let mut package_names: Vec<String> = imports_map.iter().map(|entry| entry.key().to_string()).collect();
+        package_names.sort();
+        package_names
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn broken_symlink_is_skipped() {
+        let folder = fixture("broken-symlink", &[("a/A.java", "package a;\nimport b.B;\n")]);
+        std::os::unix::fs::symlink(folder.join("missing.java"), folder.join("Broken.java")).unwrap();
+
+        let imports_map = traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, None);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(package_names(&imports_map), vec!["a"]);
+    }
+
+    #[test]
+    fn excluded_and_gitignored_folders_are_not_scanned() {
+        let folder = fixture(
+            "exclude",
+            &[
+                ("src/a/A.java", "package a;\n"),
+                ("build/gen/G.java", "package gen;\n"),
+                ("out/o/O.java", "package o;\n"),
+                (".gitignore", "out/\n"),
+            ],
+        );
+
+        let mut exclude = GlobSetBuilder::new();
+        exclude.add(Glob::new("**/build/**").unwrap());
+
+        let walk_filter = WalkFilter {
+            exclude: Arc::new(exclude.build().unwrap()),
+            respect_gitignore: true,
+            ..WalkFilter::default()
+        };
+
+        let imports_map = traverse_folder_par(folder.clone(), None, &walk_filter, None, None);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(package_names(&imports_map), vec!["a"]);
+    }
+
+    #[test]
+    fn files_of_one_package_merge_their_imports() {
+        let folder = fixture(
+            "same-package",
+            &[
+                ("com/foo/A.java", "package com.foo;\nimport com.bar.B;\nimport java.util.List;\n"),
+                ("com/foo/C.java", "package com.foo;\nimport com.baz.D;\nimport java.util.List;\n"),
+            ],
+        );
+
+        let imports_map = traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, None);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(
+            imports_map.get("com.foo").unwrap().clone(),
+            vec!["com.bar.B", "com.baz.D", "java.util.List"],
+        );
+    }
+
+    #[test]
+    fn only_the_files_of_a_manifest_are_parsed() {
+        let folder = fixture(
+            "manifest",
+            &[
+                ("a/A.java", "package a;\n"),
+                ("b/B.java", "package b;\n"),
+                ("c/C.java", "package c;\n"),
+            ],
+        );
+
+        let manifest = folder.join("files.txt");
+        fs::write(&manifest, format!("{}\n\n{}\n", folder.join("a/A.java").display(), folder.join("c/C.java").display())).unwrap();
+
+        let listed_files = read_file_list(manifest.to_str().unwrap()).unwrap();
+        let imports_map = parse_listed_files(&listed_files, None, &WalkFilter::default(), None, None);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(package_names(&imports_map), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn source_roots_are_combined_into_one_map() {
+        let module_a = fixture("root-a", &[
+            ("com/a/A.java", "package com.a;\nimport com.b.B;\n"),
+            ("com/shared/S.java", "package com.shared;\nimport java.util.List;\n"),
+        ]);
+        let module_b = fixture("root-b", &[
+            ("com/b/B.java", "package com.b;\n"),
+            ("com/shared/T.java", "package com.shared;\nimport java.io.File;\n"),
+        ]);
+
+        let imports_map = traverse_folder_par(module_a.clone(), None, &WalkFilter::default(), None, None);
+        merge_imports_maps(&imports_map, traverse_folder_par(module_b.clone(), None, &WalkFilter::default(), None, None));
+
+        fs::remove_dir_all(&module_a).unwrap();
+        fs::remove_dir_all(&module_b).unwrap();
+
+        assert_eq!(package_names(&imports_map), vec!["com.a", "com.b", "com.shared"]);
+        assert_eq!(
+            imports_map.get("com.shared").unwrap().clone(),
+            vec!["java.io.File", "java.util.List"],
+        );
+    }
+
+    #[test]
+    fn module_requires_become_edges_with_modules_enabled() {
+        let folder = fixture(
+            "modules",
+            &[
+                (
+                    "module-info.java",
+                    "module com.acme.app {\n    requires com.acme.core;\n    requires transitive java.sql;\n    exports com.acme.app.api;\n}\n",
+                ),
+                ("com/acme/app/App.java", "package com.acme.app;\n"),
+            ],
+        );
+
+        let without_modules = traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, None);
+        let with_modules =
+            traverse_folder_par(folder.clone(), None, &WalkFilter { modules: true, ..WalkFilter::default() }, None, None);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(package_names(&without_modules), vec!["com.acme.app"]);
+        assert_eq!(
+            with_modules.get("com.acme.app").unwrap().clone(),
+            vec!["com.acme.core", "java.sql"],
+        );
+    }
+
+    #[test]
+    fn progress_is_reported_once_per_parsed_file() {
+        let folder = fixture(
+            "progress",
+            &[
+                ("a/A.java", "package a;\n"),
+                ("a/B.java", "package a;\n"),
+                ("b/C.kt", "package b\n"),
+                ("b/README.md", "package c;\n"),
+            ],
+        );
+
+        let files_done = AtomicUsize::new(0);
+        let progress = || {
+            files_done.fetch_add(1, Ordering::Relaxed);
+        };
+
+        traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, Some(&progress));
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(files_done.into_inner(), 3);
+    }
+
+    #[test]
+    fn files_without_a_package_land_in_the_default_package() {
+        let folder = fixture(
+            "default-package",
+            &[("Main.java", "import com.acme.core.Service;\n\nclass Main {}\n")],
+        );
+
+        let imports_map = traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, None);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(
+            imports_map.get(DEFAULT_PACKAGE).unwrap().clone(),
+            vec!["com.acme.core.Service"],
+        );
+    }
+
+    #[test]
+    fn single_threaded_traversal_builds_the_same_map() {
+        let folder = fixture(
+            "one-job",
+            &[
+                ("com/a/A.java", "package com.a;\nimport com.b.B;\nimport java.util.List;\n"),
+                ("com/a/sub/C.java", "package com.a;\nimport com.c.C;\n"),
+                ("com/b/B.java", "package com.b;\nimport com.c.C;\n"),
+                ("com/c/C.java", "package com.c;\n"),
+            ],
+        );
+
+        let one_thread = rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+        let sequential = one_thread.install(|| traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, None));
+        let parallel = traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, None);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        let sorted = |imports_map: DashMap<String, Vec<String>>| {
+            let mut packages: Vec<(String, Vec<String>)> = imports_map.into_iter().collect();
+            packages.sort();
+            packages
+        };
+
+        assert_eq!(sequential.get("com.a").unwrap().clone(), vec!["com.b.B", "com.c.C", "java.util.List"]);
+        assert_eq!(sorted(sequential), sorted(parallel));
+    }
+
+    #[test]
+    fn java_entries_of_an_archive_are_parsed() {
+        let folder = fixture("archive", &[]);
+        fs::create_dir_all(&folder).unwrap();
+        let archive_path = folder.join("acme-sources.jar");
+
+        let mut archive = zip::ZipWriter::new(fs::File::create(&archive_path).unwrap());
+        for (entry_name, content) in [
+            ("com/acme/app/App.java", "package com.acme.app;\nimport com.acme.core.Service;\n"),
+            ("com/acme/core/Service.java", "package com.acme.core;\nimport java.util.List;\n"),
+            ("META-INF/MANIFEST.MF", "Manifest-Version: 1.0\n"),
+        ] {
+            archive.start_file(entry_name, zip::write::SimpleFileOptions::default()).unwrap();
+            archive.write_all(content.as_bytes()).unwrap();
+        }
+        archive.finish().unwrap();
+
+        let imports_map = parse_archive(&archive_path, None, &WalkFilter::default(), None).unwrap();
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(package_names(&imports_map), vec!["com.acme.app", "com.acme.core"]);
+        assert_eq!(imports_map.get("com.acme.app").unwrap().clone(), vec!["com.acme.core.Service"]);
+    }
+
+    #[test]
+    fn test_sources_are_tagged_or_excluded() {
+        let folder = fixture(
+            "test-sources",
+            &[
+                ("src/main/java/com/acme/App.java", "package com.acme;\nimport com.acme.core.Service;\n"),
+                ("src/test/java/com/acme/AppTest.java", "package com.acme;\nimport org.junit.Test;\n"),
+            ],
+        );
+
+        let scan = |walk_filter: WalkFilter| traverse_folder_par(folder.clone(), None, &walk_filter, None, None);
+
+        let mixed = scan(WalkFilter::default());
+        let separated = scan(WalkFilter { separate_tests: true, ..WalkFilter::default() });
+        let without_tests = scan(WalkFilter { exclude_tests: true, ..WalkFilter::default() });
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(mixed.get("com.acme").unwrap().clone(), vec!["com.acme.core.Service", "org.junit.Test"]);
+        assert_eq!(package_names(&separated), vec!["[test] com.acme", "com.acme"]);
+        assert_eq!(separated.get("[test] com.acme").unwrap().clone(), vec!["org.junit.Test"]);
+        assert_eq!(without_tests.get("com.acme").unwrap().clone(), vec!["com.acme.core.Service"]);
+        assert_eq!(package_names(&without_tests), vec!["com.acme"]);
+    }
+
+    #[test]
+    fn package_declarations_not_matching_their_folder_are_found() {
+        let folder = fixture(
+            "layout",
+            &[
+                ("src/main/java/com/acme/app/App.java", "package com.acme.app;\n"),
+                ("src/main/java/com/acme/core/Service.java", "package com.acme.services;\n"),
+            ],
+        );
+        let source_root = folder.join("src/main/java");
+
+        let mismatches = find_package_mismatches(&folder, &source_root, &WalkFilter::default());
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(
+            mismatches,
+            vec![(
+                source_root.join("com/acme/core/Service.java"),
+                "com.acme.services".to_string(),
+                "com.acme.core".to_string(),
+            )],
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_loops_are_scanned_once() {
+        let folder = fixture("symlink-loop", &[("com/a/A.java", "package com.a;\nimport com.b.B;\n")]);
+        std::os::unix::fs::symlink(&folder, folder.join("com/a/loop")).unwrap();
+
+        let progress_count = AtomicUsize::new(0);
+        let progress = || {
+            progress_count.fetch_add(1, Ordering::Relaxed);
+        };
+
+        let not_followed = traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, None);
+        let followed = traverse_folder_par(
+            folder.clone(),
+            None,
+            &WalkFilter { follow_symlinks: true, ..WalkFilter::default() },
+            None,
+            Some(&progress),
+        );
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        assert_eq!(package_names(&not_followed), vec!["com.a"]);
+        assert_eq!(package_names(&followed), vec!["com.a"]);
+        assert_eq!(progress_count.into_inner(), 1);
+    }
+
+    #[test]
+    fn canned_bazel_query_output_selects_the_scanned_files() {
+        let workspace = fixture(
+            "bazel",
+            &[
+                ("com/acme/app/App.java", "package com.acme.app;\nimport com.acme.core.Service;\n"),
+                ("com/acme/core/Service.java", "package com.acme.core;\n"),
+                ("com/acme/unused/Unused.java", "package com.acme.unused;\n"),
+                (
+                    "query.txt",
+                    "//com/acme/app:App.java\n\
+                     //com/acme/core:Service.java\n\
+                     //com/acme/core:BUILD\n\
+                     @maven//:com_google_guava_guava\n",
+                ),
+            ],
+        );
+
+        let labels: Vec<String> = read_file_list(workspace.join("query.txt").to_str().unwrap())
+            .unwrap()
+            .iter()
+            .map(|label| label.to_string_lossy().to_string())
+            .collect();
+        let listed_files = resolve_bazel_labels(&workspace, &labels);
+
+        let imports_map = parse_listed_files(&listed_files, None, &WalkFilter::default(), None, None);
+
+        fs::remove_dir_all(&workspace).unwrap();
+
+        assert_eq!(
+            listed_files,
+            vec![
+                workspace.join("com/acme/app/App.java"),
+                workspace.join("com/acme/core/Service.java"),
+                workspace.join("com/acme/core/BUILD"),
+            ],
+        );
+        assert_eq!(package_names(&imports_map), vec!["com.acme.app", "com.acme.core"]);
+    }
+
+    #[test]
+    fn churn_counts_a_commit_once_per_renamed_node() {
+        let folder = fixture(
+            "churn",
+            &[
+                ("com/acme/a/A.java", "package com.acme.a;\n"),
+                ("com/acme/b/B.java", "package com.acme.b;\n"),
+                ("org/c/C.java", "package org.c;\n"),
+            ],
+        );
+
+        git(&folder, &["init", "-q"]);
+        git(&folder, &["add", "."]);
+        git(&folder, &["commit", "-qm", "add the sources"]);
+
+        // the second commit touches both packages below com.acme
+        fs::write(folder.join("com/acme/a/A.java"), "package com.acme.a;\nimport org.c.C;\n").unwrap();
+        fs::write(folder.join("com/acme/b/B.java"), "package com.acme.b;\nimport org.c.C;\n").unwrap();
+        git(&folder, &["commit", "-qam", "import org.c"]);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        traverse_folder_par(folder.clone(), Some(&sender), &WalkFilter::default(), None, None);
+        drop(sender);
+        let parsed_files: Vec<ParsedFile> = receiver.iter().collect();
+
+        let scan_roots = [folder.clone()];
+        let package_churn = count_package_churn(&parsed_files, &scan_roots, "1970-01-01", |package_name| {
+            package_name.to_string()
+        });
+        let prefix_churn = count_package_churn(&parsed_files, &scan_roots, "1970-01-01", |package_name| {
+            package_prefix(package_name, 2).to_string()
+        });
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        let churn = |counts: &[(&str, usize)]| -> HashMap<String, usize> {
+            counts.iter().map(|(node_name, commits)| (node_name.to_string(), *commits)).collect()
+        };
+
+        assert_eq!(package_churn, churn(&[("com.acme.a", 2), ("com.acme.b", 2), ("org.c", 1)]));
+        assert_eq!(prefix_churn, churn(&[("com.acme", 2), ("org.c", 1)]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn listed_source_files_follow_the_walk_filter() {
+        let folder = fixture(
+            "list-sources",
+            &[
+                ("src/com/a/A.java", "package com.a;\n"),
+                ("src/com/a/B.kt", "package com.a\n"),
+                ("src/com/a/C.scala", "package com.a\n"),
+                ("build/gen/G.java", "package gen;\n"),
+                ("out/o/O.java", "package o;\n"),
+                (".gitignore", "out/\n"),
+            ],
+        );
+        let linked = fixture("list-sources-linked", &[("x/X.java", "package x;\n")]);
+        std::os::unix::fs::symlink(&linked, folder.join("src/linked")).unwrap();
+        std::os::unix::fs::symlink(folder.join("src"), folder.join("src/com/loop")).unwrap();
+
+        let mut exclude = GlobSetBuilder::new();
+        exclude.add(Glob::new("**/build/**").unwrap());
+
+        let walk_filter = WalkFilter {
+            exclude: Arc::new(exclude.build().unwrap()),
+            respect_gitignore: true,
+            languages: vec![Language::Java, Language::Scala],
+            ..WalkFilter::default()
+        };
+
+        let source_files = collect_source_files(&folder, &walk_filter);
+        let linked_files = collect_source_files(&folder, &WalkFilter { follow_symlinks: true, ..walk_filter });
+
+        fs::remove_dir_all(&folder).unwrap();
+        fs::remove_dir_all(&linked).unwrap();
+
+        assert_eq!(source_files, vec![folder.join("src/com/a/A.java"), folder.join("src/com/a/C.scala")]);
+        // followed links are entered, but the one back to src only once
+        assert_eq!(
+            linked_files,
+            vec![
+                folder.join("src/com/a/A.java"),
+                folder.join("src/com/a/C.scala"),
+                folder.join("src/linked/x/X.java"),
+            ],
+        );
+    }
+
+    #[test]
+    #[ignore = "times the walk; run with --release -- --ignored --nocapture"]
+    fn walk_of_a_flat_folder_of_ten_thousand_files_is_timed() {
+        let files: Vec<(String, String)> = (0..10_000)
+            .map(|index| {
+                (
+                    format!("com/acme/C{}.java", index),
+                    format!("package com.acme;\nimport com.lib.p{}.Type;\n", index % 500),
+                )
+            })
+            .collect();
+        let files: Vec<(&str, &str)> = files.iter().map(|(file_name, content)| (file_name.as_str(), content.as_str())).collect();
+
+        let folder = fixture("walk-timing", &files);
+
+        let time_walk = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            let started = Instant::now();
+            let imports_map = pool.install(|| traverse_folder_par(folder.clone(), None, &WalkFilter::default(), None, None));
+            let elapsed = started.elapsed();
+
+            let imports = imports_map.get("com.acme").map_or(0, |imports| imports.len());
+            (elapsed, imports)
+        };
+
+        let all_threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+
+        // the first walk warms the page cache for both timed ones
+        time_walk(all_threads);
+        let (sequential, sequential_imports) = time_walk(1);
+        let (parallel, parallel_imports) = time_walk(all_threads);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        println!("1 thread: {:.2?}, {} threads: {:.2?}", sequential, all_threads, parallel);
+        assert_eq!(sequential_imports, 500);
+        assert_eq!(parallel_imports, 500);
+    }
+
+    #[test]
+    fn wildcard_imports_resolve_to_the_one_package_declaring_a_used_class() {
+        let folder = fixture(
+            "wildcards",
+            &[
+                (
+                    "com/acme/app/App.java",
+                    "package com.acme.app;\nimport com.acme.util.*;\nimport com.acme.model.*;\nimport com.acme.other.*;\n\
+                     class App { Strings strings; Order order; Shared shared; }\n",
+                ),
+                ("com/acme/util/Strings.java", "package com.acme.util;\npublic class Strings {}\n"),
+                ("com/acme/model/Order.java", "package com.acme.model;\npublic class Order {}\nclass Shared {}\n"),
+                ("com/acme/other/Shared.java", "package com.acme.other;\npublic class Shared {}\n"),
+            ],
+        );
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let imports_map = traverse_folder_par(folder.clone(), Some(&sender), &WalkFilter::default(), None, None);
+        drop(sender);
+        let parsed_files: Vec<ParsedFile> = receiver.iter().collect();
+
+        let ambiguities = resolve_wildcard_imports(&parsed_files, &imports_map);
+
+        fs::remove_dir_all(&folder).unwrap();
+
+        // Shared is declared by two of the wildcard packages, which therefore stay
+        assert_eq!(
+            imports_map.get("com.acme.app").unwrap().clone(),
+            vec!["com.acme.model.*", "com.acme.other.*", "com.acme.model.Order", "com.acme.util.Strings"],
+        );
+        assert_eq!(ambiguities.len(), 1);
+        assert!(ambiguities[0].0.ends_with("App.java"));
+        assert_eq!(ambiguities[0].1, "Shared");
+        assert_eq!(ambiguities[0].2, vec!["com.acme.model", "com.acme.other"]);
+    }
+}
@@ -0,0 +1,69 @@
+// Fixtures shared by the unit tests of the library and of the binary, which
+// compiles this file again as a module of its own
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use dashmap::DashMap;
+
+// Function to build an imports map from (package, imports) pairs
+pub(crate) fn imports_map(
+    packages: &[(&str, &[&str])],
+) -> DashMap<String, Vec<String>> {
+    packages
+        .iter()
+        .map(|(package_name, imports)| {
+            (package_name.to_string(), imports.iter().map(|import_value| import_value.to_string()).collect())
+        })
+        .collect()
+}
+
+// Function to write source files below a fresh folder in the temp dir
+pub(crate) fn fixture(
+    name: &str,
+    files: &[(&str, &str)],
+) -> PathBuf {
+    let folder = std::env::temp_dir().join(format!("jadep-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&folder);
+    fs::create_dir_all(&folder).unwrap();
+
+    for (file_name, content) in files {
+        let file_path = folder.join(file_name);
+        fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+        fs::write(file_path, content).unwrap();
+    }
+
+    folder
+}
+
+// Function to write a shell script standing in for graphviz into a folder
+#[cfg(unix)]
+pub(crate) fn fake_dot(
+    folder: &Path,
+    script: &str,
+) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dot_bin = folder.join("dot");
+    fs::write(&dot_bin, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(&dot_bin, fs::Permissions::from_mode(0o755)).unwrap();
+
+    dot_bin
+}
+
+// Function to run git in a folder, failing the test if git fails
+pub(crate) fn git(
+    folder: &Path,
+    args: &[&str],
+) {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(folder)
+        .args(["-c", "user.name=jadep", "-c", "user.email=jadep@example.com"])
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
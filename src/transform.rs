@@ -0,0 +1,655 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::str::FromStr;
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::scan::dedup_imports;
+
+// Function to find the scanned package an import belongs to, i.e. the longest
+// scanned package that equals the import or is one of its dotted prefixes
+pub(crate) fn owning_package<'a>(
+    import_value: &'a str,
+    internal_packages: &HashSet<String>,
+) -> Option<&'a str> {
+    if internal_packages.contains(import_value) {
+        return Some(import_value);
+    }
+
+    import_value
+        .rmatch_indices('.')
+        .map(|(position, _)| &import_value[..position])
+        .find(|prefix| internal_packages.contains(*prefix))
+}
+
+// Function to check whether an import refers to one of the scanned packages
+pub fn is_internal(
+    import_value: &str,
+    internal_packages: &HashSet<String>,
+) -> bool {
+    owning_package(import_value, internal_packages).is_some()
+}
+
+// Function to build the package-level graph of the scanned packages, mapping
+// every package to the other scanned packages it imports from
+pub(crate) fn build_internal_graph(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> HashMap<String, HashSet<String>> {
+    let internal_packages: HashSet<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+
+    imports_map
+        .iter()
+        .map(|entry| {
+            let dependencies = entry
+                .value()
+                .iter()
+                .filter_map(|import_value| owning_package(import_value, &internal_packages))
+                .filter(|dependency| *dependency != entry.key())
+                .map(|dependency| dependency.to_string())
+                .collect();
+
+            (entry.key().to_string(), dependencies)
+        })
+        .collect()
+}
+
+// Function to invert a package-level graph, mapping every package to its dependents
+pub(crate) fn invert_internal_graph(
+    internal_graph: &HashMap<String, HashSet<String>>,
+) -> HashMap<String, HashSet<String>> {
+    let mut dependents: HashMap<String, HashSet<String>> = internal_graph
+        .keys()
+        .map(|package_name| (package_name.clone(), HashSet::new()))
+        .collect();
+
+    for (package_name, dependencies) in internal_graph {
+        for dependency in dependencies {
+            dependents
+                .entry(dependency.clone())
+                .or_default()
+                .insert(package_name.clone());
+        }
+    }
+
+    dependents
+}
+
+// Function to find the package an import names: the scanned package owning it,
+// else everything before its first capitalized (class) segment, else the import
+// itself for wildcards and all but its last segment otherwise
+pub fn import_package<'a>(
+    import_value: &'a str,
+    internal_packages: &HashSet<String>,
+) -> &'a str {
+    let (import_value, is_wildcard) =
+        match import_value.strip_suffix(".*") {
+            Some(import_value) => (import_value, true),
+            None => (import_value, false),
+        };
+
+    if let Some(package_name) = owning_package(import_value, internal_packages) {
+        return package_name;
+    }
+
+    let class_start = import_value
+        .match_indices('.')
+        .find(|(position, _)| {
+            import_value[position + 1..].starts_with(|c: char| c.is_ascii_uppercase())
+        });
+
+    if class_start.is_none() && is_wildcard {
+        return import_value;
+    }
+
+    match class_start.or_else(|| import_value.rmatch_indices('.').next()) {
+        Some((position, _)) => &import_value[..position],
+        None => import_value,
+    }
+}
+
+// Function to find the outer class of an import of a nested type, going by the naming
+// convention: when the segment after the first capitalized (class) segment is also
+// capitalized, the import is cut back to that first class, e.g. com.foo.Outer.Inner
+// becomes com.foo.Outer; other imports are returned unchanged
+pub fn outer_type(
+    import_value: &str,
+) -> &str {
+    let is_capitalized = |segment: &str| segment.starts_with(|c: char| c.is_ascii_uppercase());
+
+    let mut class_end = None;
+
+    for (position, _) in import_value.match_indices('.') {
+        let next_segment = import_value[position + 1..].split('.').next().unwrap_or_default();
+
+        match class_end {
+            Some(class_end) if is_capitalized(next_segment) => return &import_value[..class_end],
+            Some(_) => return import_value,
+            None if is_capitalized(next_segment) => {
+                class_end = Some(position + 1 + next_segment.len());
+            }
+            None => {}
+        }
+    }
+
+    import_value
+}
+
+// Function to replace every import of a nested type with an import of its outer class
+pub fn merge_nested_imports(
+    imports_map: &DashMap<String, Vec<String>>,
+) {
+    for mut entry in imports_map.iter_mut() {
+        let mut seen = HashSet::new();
+        let imports = std::mem::take(entry.value_mut());
+
+        *entry.value_mut() = imports
+            .iter()
+            .map(|import_value| outer_type(import_value).to_string())
+            .filter(|import_value| seen.insert(import_value.clone()))
+            .collect();
+    }
+}
+
+// Function to count every import as an edge of weight one, before any collapse
+// merges several imports into a single edge
+pub fn count_edge_weights(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> HashMap<(String, String), usize> {
+    imports_map
+        .iter()
+        .flat_map(|entry| {
+            let package_name = entry.key().to_string();
+
+            entry
+                .value()
+                .iter()
+                .map(move |import_value| ((package_name.clone(), import_value.to_string()), 1))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// Function to rename both ends of every weighted edge, summing the weights of
+// edges that end up the same and dropping edges from a node to itself
+pub fn rename_edge_weights(
+    edge_weights: &HashMap<(String, String), usize>,
+    rename_package: impl Fn(&str) -> String,
+    rename_import: impl Fn(&str) -> String,
+) -> HashMap<(String, String), usize> {
+    let mut renamed = HashMap::<(String, String), usize>::new();
+
+    for ((package_name, import_value), weight) in edge_weights {
+        let package_key = rename_package(package_name);
+        let import_key = rename_import(import_value);
+
+        if package_key != import_key {
+            *renamed.entry((package_key, import_key)).or_default() += weight;
+        }
+    }
+
+    renamed
+}
+
+// Function to rename the packages source files were found in, merging the files
+// of packages that end up the same
+pub fn rename_package_files(
+    package_files: BTreeMap<String, Vec<String>>,
+    rename: impl Fn(&str) -> String,
+) -> BTreeMap<String, Vec<String>> {
+    let mut renamed = BTreeMap::<String, Vec<String>>::new();
+
+    for (package_name, files) in package_files {
+        renamed.entry(rename(&package_name)).or_default().extend(files);
+    }
+
+    renamed
+}
+
+// Function to replace every import with the package it names, dropping imports
+// of the importing package itself
+pub fn collapse_imports_to_packages(
+    imports_map: &DashMap<String, Vec<String>>,
+) {
+    let internal_packages: HashSet<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+
+    for mut entry in imports_map.iter_mut() {
+        let package_name = entry.key().to_string();
+        let mut seen = HashSet::new();
+        let imports = std::mem::take(entry.value_mut());
+
+        *entry.value_mut() = imports
+            .iter()
+            .map(|import_value| import_package(import_value, &internal_packages).to_string())
+            .filter(|import_value| *import_value != package_name)
+            .filter(|import_value| seen.insert(import_value.clone()))
+            .collect();
+    }
+}
+
+// Function to lowercase imports that target packages outside the scanned folder
+pub fn normalize_external_case(
+    imports_map: &DashMap<String, Vec<String>>,
+) {
+    let internal_packages: HashSet<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+
+    for mut entry in imports_map.iter_mut() {
+        let mut seen = HashSet::new();
+        let imports = std::mem::take(entry.value_mut());
+
+        *entry.value_mut() = imports
+            .into_iter()
+            .map(|import_value| {
+                if is_internal(&import_value, &internal_packages) {
+                    import_value
+                } else {
+                    import_value.to_lowercase()
+                }
+            })
+            .filter(|import_value| seen.insert(import_value.clone()))
+            .collect();
+    }
+}
+
+// Function to check whether a name is the given prefix or lies below it, so that
+// `com.acme.crypto` covers `com.acme.crypto.Cipher` but not `com.acme.cryptography`
+pub(crate) fn is_under_prefix(
+    name: &str,
+    prefix: &str,
+) -> bool {
+    name.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+// Function to keep only imports targeting one of the given prefixes,
+// dropping packages that are left without any imports
+pub fn keep_edges_to(
+    imports_map: &DashMap<String, Vec<String>>,
+    target_prefixes: &[String],
+) {
+    imports_map.retain(|_, imports| {
+        imports.retain(|import_value| {
+            target_prefixes
+                .iter()
+                .any(|target_prefix| is_under_prefix(import_value, target_prefix))
+        });
+
+        !imports.is_empty()
+    });
+}
+
+// Function to invert an imports map so every edge points from an imported
+// name to the packages importing it
+pub fn invert_imports_map(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> DashMap<String, Vec<String>> {
+    let dependents: DashMap<String, Vec<String>> = imports_map
+        .iter()
+        .map(|entry| (entry.key().to_string(), Vec::new()))
+        .collect();
+
+    for entry in imports_map.iter() {
+        for import_value in entry.value() {
+            dependents
+                .entry(import_value.to_string())
+                .or_default()
+                .push(entry.key().to_string());
+        }
+    }
+
+    dedup_imports(&dependents);
+
+    dependents
+}
+
+// Function to rename every package and import, merging the edges of packages
+// that end up with the same name and dropping edges within one name
+fn rename_nodes(
+    imports_map: &DashMap<String, Vec<String>>,
+    rename: impl Fn(&str) -> String,
+) -> DashMap<String, Vec<String>> {
+    let renamed = DashMap::<String, Vec<String>>::new();
+
+    for entry in imports_map.iter() {
+        let package_key = rename(entry.key());
+        let mut imports = renamed.entry(package_key.clone()).or_default();
+
+        for import_value in entry.value() {
+            let import_key = rename(import_value);
+
+            if import_key != package_key && !imports.contains(&import_key) {
+                imports.push(import_key);
+            }
+        }
+    }
+
+    renamed
+}
+
+// Function to rewrite every package and import to the first capture group of
+// node_key (leaving non-matching names unchanged), merging the resulting edges
+pub fn rekey_imports_map(
+    imports_map: &DashMap<String, Vec<String>>,
+    node_key: &regex::Regex,
+) -> DashMap<String, Vec<String>> {
+    rename_nodes(imports_map, |name| rekey_name(name, node_key))
+}
+
+// Function to get the first capture group of node_key in a name, or the name itself
+pub fn rekey_name(
+    name: &str,
+    node_key: &regex::Regex,
+) -> String {
+    node_key
+        .captures(name)
+        .and_then(|captures| captures.get(1))
+        .map(|key| key.as_str().to_string())
+        .unwrap_or_else(|| name.to_string())
+}
+
+// Function to rewrite every package and import to its first `segments` segments,
+// merging the resulting edges
+pub fn collapse_imports_map(
+    imports_map: &DashMap<String, Vec<String>>,
+    segments: usize,
+) -> DashMap<String, Vec<String>> {
+    rename_nodes(imports_map, |name| package_prefix(name, segments).to_string())
+}
+
+// Function to get the first `segments` dot-separated segments of a package name
+pub fn package_prefix(
+    package_name: &str,
+    segments: usize,
+) -> &str {
+    if segments == 0 {
+        return "";
+    }
+
+    match package_name.match_indices('.').nth(segments - 1) {
+        Some((position, _)) => &package_name[..position],
+        None => package_name,
+    }
+}
+
+// Function to partition the packages by their first `segments` segments; every
+// partition keeps the imports within it, while the imports of anything outside it
+// become a single edge to a node named after the prefix they fall under
+pub fn partition_by_prefix(
+    imports_map: &DashMap<String, Vec<String>>,
+    segments: usize,
+) -> BTreeMap<String, DashMap<String, Vec<String>>> {
+    let mut partitions = BTreeMap::<String, DashMap<String, Vec<String>>>::new();
+
+    for entry in imports_map.iter() {
+        let partition_prefix = package_prefix(entry.key(), segments);
+        let mut seen = HashSet::new();
+
+        let imports = entry
+            .value()
+            .iter()
+            .map(|import_value| {
+                let import_prefix = package_prefix(import_value, segments);
+
+                if import_prefix == partition_prefix {
+                    import_value.to_string()
+                } else {
+                    import_prefix.to_string()
+                }
+            })
+            .filter(|import_value| seen.insert(import_value.clone()))
+            .collect();
+
+        partitions
+            .entry(partition_prefix.to_string())
+            .or_default()
+            .insert(entry.key().to_string(), imports);
+    }
+
+    partitions
+}
+
+// Function to drop imports between packages sharing the same top-level package
+pub fn drop_same_top_level_edges(
+    imports_map: &DashMap<String, Vec<String>>,
+    segments: usize,
+) {
+    for mut entry in imports_map.iter_mut() {
+        let top_level = package_prefix(entry.key(), segments).to_string();
+
+        entry
+            .value_mut()
+            .retain(|import_value| package_prefix(import_value, segments) != top_level);
+    }
+}
+
+// Function to flatten a dependency tree into its edges sorted by (source, target),
+// so the generated text is identical across runs despite the map's iteration order
+pub(crate) fn sorted_edges(
+    dependency_tree: DashMap<String, Vec<String>>,
+) -> Vec<(String, String)> {
+    let mut edges: Vec<(String, String)> = dependency_tree
+        .into_iter()
+        .flat_map(|(package_name, imports)| {
+            imports
+                .into_iter()
+                .map(move |import_value| (package_name.clone(), import_value))
+        })
+        .collect();
+
+    edges.sort();
+
+    edges
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Granularity {
+    #[serde(rename = "class")]
+    Class,
+    #[serde(rename = "package")]
+    Package,
+}
+
+impl FromStr for Granularity {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "class" => Ok(Granularity::Class),
+            "package" => Ok(Granularity::Package),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::imports_map;
+    use crate::render::{generate_dot_content, GraphStyle};
+    use crate::tree::TreeOptions;
+    use crate::render::weight_edge_attributes;
+
+    #[test]
+    fn reversed_graph_points_from_imported_to_importing_package() {
+        let imports_map = imports_map(&[("a", &["b"]), ("b", &[])]);
+
+        let dot_content = generate_dot_content(
+            &invert_imports_map(&imports_map),
+            None,
+            &TreeOptions::default(),
+            &HashMap::new(),
+            &HashMap::new(),
+            &GraphStyle::default(),
+        );
+
+        assert!(dot_content.contains("\"b\" -> \"a\";"));
+        assert!(!dot_content.contains("\"a\" -> \"b\""));
+    }
+
+    #[test]
+    fn kept_edge_targets_stop_at_a_segment_boundary() {
+        let imports_map = imports_map(&[
+            ("com.acme.app", &["com.acme.crypto", "com.acme.crypto.Cipher", "com.acme.cryptography.Hash"]),
+            ("com.acme.util", &["com.acme.cryptography"]),
+        ]);
+
+        keep_edges_to(&imports_map, &["com.acme.crypto".to_string()]);
+
+        assert_eq!(
+            sorted_edges(imports_map),
+            vec![
+                ("com.acme.app".to_string(), "com.acme.crypto".to_string()),
+                ("com.acme.app".to_string(), "com.acme.crypto.Cipher".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn package_granularity_lands_imports_on_package_nodes() {
+        let class_map = imports_map(&[
+            ("com.foo.app", &["com.foo.bar.Baz", "com.foo.bar.Qux.Inner", "com.foo.app.Local", "org.lib.util.Helper", "org.x.*"]),
+            ("com.foo.bar", &[]),
+        ]);
+
+        let package_map = class_map.clone();
+        collapse_imports_to_packages(&package_map);
+
+        let edge = |package_name: &str, import_value: &str| (package_name.to_string(), import_value.to_string());
+
+        // class granularity keeps the imports as written
+        assert_eq!(
+            sorted_edges(class_map),
+            vec![
+                edge("com.foo.app", "com.foo.app.Local"),
+                edge("com.foo.app", "com.foo.bar.Baz"),
+                edge("com.foo.app", "com.foo.bar.Qux.Inner"),
+                edge("com.foo.app", "org.lib.util.Helper"),
+                edge("com.foo.app", "org.x.*"),
+            ],
+        );
+        assert_eq!(
+            sorted_edges(package_map),
+            vec![
+                edge("com.foo.app", "com.foo.bar"),
+                edge("com.foo.app", "org.lib.util"),
+                edge("com.foo.app", "org.x"),
+            ],
+        );
+    }
+
+    #[test]
+    fn collapsed_packages_merge_their_edges_and_drop_self_edges() {
+        let imports_map = imports_map(&[
+            ("com.a.x", &["com.b.y.Y", "com.a.z.Z"]),
+            ("com.a.z", &["com.b.w.W"]),
+            ("com.b.y", &[]),
+        ]);
+
+        let collapsed = collapse_imports_map(&imports_map, 2);
+
+        let mut packages: Vec<(String, Vec<String>)> = collapsed
+            .into_iter()
+            .collect();
+        packages.sort();
+
+        assert_eq!(
+            packages,
+            vec![
+                ("com.a".to_string(), vec!["com.b".to_string()]),
+                ("com.b".to_string(), vec![]),
+            ],
+        );
+    }
+
+    #[test]
+    fn edge_weights_are_summed_when_imports_collapse_to_packages() {
+        let imports_map = imports_map(&[
+            ("com.a", &["com.b.X", "com.b.Y", "com.b.Z", "com.c.W"]),
+            ("com.b", &[]),
+            ("com.c", &[]),
+        ]);
+        let internal_packages: HashSet<String> = imports_map
+            .iter()
+            .map(|entry| entry.key().to_string())
+            .collect();
+
+        let edge_weights = rename_edge_weights(
+            &count_edge_weights(&imports_map),
+            |package_name| package_name.to_string(),
+            |import_value| import_package(import_value, &internal_packages).to_string(),
+        );
+        collapse_imports_to_packages(&imports_map);
+
+        let dot_content = generate_dot_content(
+            &imports_map,
+            None,
+            &TreeOptions::default(),
+            &HashMap::new(),
+            &weight_edge_attributes(&edge_weights),
+            &GraphStyle::default(),
+        );
+
+        let edge_line = |import_value: &str| {
+            dot_content
+                .lines()
+                .find(|line| line.contains(&format!("\"com.a\" -> \"{}\"", import_value)))
+                .unwrap()
+                .to_string()
+        };
+
+        assert!(edge_line("com.b").contains("xlabel=\"3\""));
+        assert!(edge_line("com.b").contains("penwidth=5.00"));
+        assert!(edge_line("com.c").contains("xlabel=\"1\""));
+    }
+
+    #[test]
+    fn nested_type_imports_merge_into_their_outer_class() {
+        assert_eq!(outer_type("com.foo.Outer.Inner"), "com.foo.Outer");
+        assert_eq!(outer_type("com.foo.Outer.Inner.Deepest"), "com.foo.Outer");
+        assert_eq!(outer_type("com.foo.bar.Baz"), "com.foo.bar.Baz");
+        assert_eq!(outer_type("com.foo.Outer.method"), "com.foo.Outer.method");
+
+        let imports_map = imports_map(&[("com.app", &["com.foo.Outer.Inner", "com.foo.Outer", "com.foo.bar.Baz"])]);
+
+        merge_nested_imports(&imports_map);
+
+        assert_eq!(imports_map.get("com.app").unwrap().clone(), vec!["com.foo.Outer", "com.foo.bar.Baz"]);
+    }
+
+    #[test]
+    fn rekeyed_packages_merge_on_their_captured_key() {
+        let imports_map = imports_map(&[
+            ("com.acme.app.ui", &["com.acme.app.core", "com.acme.lib.Strings", "org.other.Util"]),
+            ("com.acme.lib.text", &["com.acme.app.ui", "com.acme.lib.Strings"]),
+            ("module-info", &[]),
+        ]);
+        let node_key = regex::Regex::new(r"^(com\.acme\.[a-z]+)").unwrap();
+
+        let rekeyed = rekey_imports_map(&imports_map, &node_key);
+
+        let mut packages: Vec<(String, Vec<String>)> = rekeyed
+            .into_iter()
+            .collect();
+        packages.sort();
+
+        // names without a match, like the external import, keep their own name
+        assert_eq!(
+            packages,
+            vec![
+                ("com.acme.app".to_string(), vec!["com.acme.lib".to_string(), "org.other.Util".to_string()]),
+                ("com.acme.lib".to_string(), vec!["com.acme.app".to_string()]),
+                ("module-info".to_string(), vec![]),
+            ],
+        );
+        // as does a key without a capture group
+        assert_eq!(rekey_name("org.other.Util", &regex::Regex::new(r"^org\.other").unwrap()), "org.other.Util");
+    }
+}
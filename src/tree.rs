@@ -0,0 +1,692 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+
+use dashmap::{DashMap, DashSet};
+
+use crate::transform::{is_internal, is_under_prefix};
+
+// Function to find the packages the dependency tree starts from: every package
+// matching the root class prefix, or all packages if there is none
+pub fn root_packages(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+) -> Vec<String> {
+    imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .filter(|package_name| {
+            root_class_prefix.is_none_or(|root_class_prefix| package_name.starts_with(root_class_prefix))
+        })
+        .collect()
+}
+
+// Function to tabulate, for every depth limit, how many nodes and edges the
+// dependency tree would contain when built with the other tree options as given,
+// stopping at the first depth that adds nothing
+pub fn depth_budget_report(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+) -> Vec<(usize, usize)> {
+    let mut report = Vec::<(usize, usize)>::new();
+
+    for depth in 0.. {
+        let dependency_tree =
+            build_dependency_tree(
+                imports_map,
+                root_class_prefix,
+                &TreeOptions {
+                    depth: Some(depth),
+                    ..tree_options.clone()
+                },
+            );
+
+        let mut nodes = HashSet::new();
+        let mut edges = 0;
+
+        for entry in dependency_tree.iter() {
+            nodes.insert(entry.key().to_string());
+            nodes.extend(entry.value().iter().cloned());
+            edges += entry.value().len();
+        }
+
+        if depth > 0 && report.last() == Some(&(nodes.len(), edges)) {
+            break;
+        }
+
+        report.push((nodes.len(), edges));
+    }
+
+    report
+}
+
+// Function to copy an imports map, keeping only imports of scanned packages
+fn keep_internal_edges(
+    imports_map: &DashMap<String, Vec<String>>,
+) -> DashMap<String, Vec<String>> {
+    let internal_packages: HashSet<String> = imports_map
+        .iter()
+        .map(|entry| entry.key().to_string())
+        .collect();
+
+    imports_map
+        .iter()
+        .map(|entry| {
+            let imports = entry
+                .value()
+                .iter()
+                .filter(|import_value| is_internal(import_value, &internal_packages))
+                .cloned()
+                .collect();
+
+            (entry.key().to_string(), imports)
+        })
+        .collect()
+}
+
+// Function to copy an imports map, keeping only imports within the target prefix
+fn keep_target_edges(
+    imports_map: &DashMap<String, Vec<String>>,
+    target_prefix: &str,
+) -> DashMap<String, Vec<String>> {
+    imports_map
+        .iter()
+        .map(|entry| {
+            let imports = entry
+                .value()
+                .iter()
+                .filter(|import_value| is_under_prefix(import_value, target_prefix))
+                .cloned()
+                .collect();
+
+            (entry.key().to_string(), imports)
+        })
+        .collect()
+}
+
+// Function to copy an imports map without the packages matching any of the patterns,
+// dropping them both as sources and as targets; with bridging, an edge into a removed
+// package is replaced by edges to the kept packages reachable through removed ones
+fn drop_excluded_packages(
+    imports_map: &DashMap<String, Vec<String>>,
+    exclude_packages: &[regex::Regex],
+    bridge: bool,
+) -> DashMap<String, Vec<String>> {
+    let is_excluded = |package_name: &str| {
+        exclude_packages
+            .iter()
+            .any(|pattern| pattern.is_match(package_name))
+    };
+
+    imports_map
+        .iter()
+        .filter(|entry| !is_excluded(entry.key()))
+        .map(|entry| {
+            let mut imports = Vec::new();
+
+            for import_value in entry.value() {
+                if !is_excluded(import_value) {
+                    imports.push(import_value.clone());
+                    continue;
+                }
+
+                if !bridge {
+                    continue;
+                }
+
+                // walk through chains of removed packages to the kept ones behind them
+                let mut visited = HashSet::from([import_value.clone()]);
+                let mut queue = VecDeque::from([import_value.clone()]);
+
+                while let Some(removed_package) = queue.pop_front() {
+                    let Some(removed_imports) = imports_map.get(&removed_package) else {
+                        continue;
+                    };
+
+                    for bridged_value in removed_imports.iter() {
+                        if is_excluded(bridged_value) {
+                            if visited.insert(bridged_value.clone()) {
+                                queue.push_back(bridged_value.clone());
+                            }
+                        } else if bridged_value != entry.key() && !imports.contains(bridged_value) {
+                            imports.push(bridged_value.clone());
+                        }
+                    }
+                }
+            }
+
+            (entry.key().to_string(), imports)
+        })
+        .collect()
+}
+
+// Function to build the dependency tree; a depth limit of N keeps N levels of
+// edges from the roots, so a depth of 0 keeps only the roots themselves
+pub fn build_dependency_tree(
+    imports_map: &DashMap<String, Vec<String>>,
+    root_class_prefix: Option<&str>,
+    tree_options: &TreeOptions,
+) -> DashMap<String, Vec<String>> {
+    let internal_map;
+    let imports_map =
+        if tree_options.internal_only {
+            internal_map = keep_internal_edges(imports_map);
+            &internal_map
+        } else {
+            imports_map
+        };
+
+    let excluded_map;
+    let imports_map =
+        if !tree_options.exclude_packages.is_empty() {
+            excluded_map = drop_excluded_packages(imports_map, &tree_options.exclude_packages, tree_options.bridge);
+            &excluded_map
+        } else {
+            imports_map
+        };
+
+    let target_map;
+    let imports_map =
+        if let Some(ref target_prefix) = tree_options.target_prefix {
+            target_map = keep_target_edges(imports_map, target_prefix);
+            &target_map
+        } else {
+            imports_map
+        };
+
+    let tree = DashMap::<String, Vec<String>>::new();
+    let visited = DashSet::<String>::new();
+
+    let roots: Vec<(String, usize)> =
+        root_packages(imports_map, root_class_prefix)
+            .into_iter()
+            .map(|package_name| (package_name, 0))
+            .collect();
+
+    let depth = tree_options.depth.unwrap_or(usize::MAX);
+
+    if let Some(node_budget) = tree_options.node_budget {
+        return build_budgeted_tree(imports_map, roots, depth, node_budget);
+    }
+
+    // breadth-first, so every package is expanded at its shortest distance from the roots
+    let mut queue: VecDeque<(String, usize)> = roots.into_iter().collect();
+
+    while let Some((package_name, current_depth)) = queue.pop_front() {
+        if !visited.insert(package_name.clone()) {
+            continue;
+        }
+
+        tree.entry(package_name.clone()).or_default();
+
+        if current_depth >= depth {
+            continue;
+        }
+
+        if let Some(imports) = imports_map.get(&package_name) {
+            // a package may import the same target several times; keep the first
+            let mut seen = HashSet::new();
+
+            for import_value in imports.iter() {
+                if !seen.insert(import_value) {
+                    continue;
+                }
+
+                tree.get_mut(&package_name)
+                    .unwrap()
+                    .push(import_value.to_string());
+
+                queue.push_back(
+                    (
+                        import_value.clone(),
+                        current_depth + 1,
+                    ),
+                );
+            }
+        }
+    }
+
+    tree
+}
+
+// Function to build the dependency tree by best-first expansion from the roots,
+// always visiting the most-connected package next until the node budget is spent
+fn build_budgeted_tree(
+    imports_map: &DashMap<String, Vec<String>>,
+    roots: Vec<(String, usize)>,
+    depth: usize,
+    node_budget: usize,
+) -> DashMap<String, Vec<String>> {
+    let mut degrees = HashMap::<String, usize>::new();
+
+    for entry in imports_map.iter() {
+        *degrees.entry(entry.key().to_string()).or_default() += entry.value().len();
+
+        for import_value in entry.value() {
+            *degrees.entry(import_value.to_string()).or_default() += 1;
+        }
+    }
+
+    let mut queue = BinaryHeap::new();
+
+    for (package_name, current_depth) in roots {
+        queue.push((usize::MAX, Reverse(package_name), current_depth));
+    }
+
+    let mut selected = HashSet::<String>::new();
+
+    while let Some((_, Reverse(package_name), current_depth)) = queue.pop() {
+        if selected.len() >= node_budget {
+            break;
+        }
+
+        if current_depth > depth || !selected.insert(package_name.clone()) {
+            continue;
+        }
+
+        if let Some(imports) = imports_map.get(&package_name) {
+            for import_value in imports.iter() {
+                if !selected.contains(import_value) {
+                    queue.push((
+                        degrees.get(import_value).copied().unwrap_or(0),
+                        Reverse(import_value.clone()),
+                        current_depth + 1,
+                    ));
+                }
+            }
+        }
+    }
+
+    let tree = DashMap::<String, Vec<String>>::new();
+
+    for package_name in &selected {
+        let imports = imports_map
+            .get(package_name)
+            .map(|imports| {
+                let mut seen = HashSet::new();
+
+                imports
+                    .iter()
+                    .filter(|import_value| selected.contains(*import_value))
+                    .filter(|import_value| seen.insert(*import_value))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        tree.insert(package_name.clone(), imports);
+    }
+
+    tree
+}
+
+// Function to compute the dominator tree of everything reachable from root,
+// returned as a map of each package to the packages it immediately dominates
+pub fn build_dominator_tree(
+    imports_map: &DashMap<String, Vec<String>>,
+    root: &str,
+) -> Option<DashMap<String, Vec<String>>> {
+    if !imports_map.contains_key(root) {
+        return None;
+    }
+
+    let successors = |package_name: &str| -> Vec<String> {
+        imports_map
+            .get(package_name)
+            .map(|imports| imports.value().clone())
+            .unwrap_or_default()
+    };
+
+    // depth-first search from the root to number nodes in postorder
+    let mut postorder = Vec::<String>::new();
+    let mut visited = HashSet::<String>::from([root.to_string()]);
+    let mut stack = vec![(root.to_string(), successors(root), 0)];
+
+    while let Some((package_name, imports, next)) = stack.last_mut() {
+        if let Some(import_value) = imports.get(*next).cloned() {
+            *next += 1;
+
+            if visited.insert(import_value.clone()) {
+                let import_successors = successors(&import_value);
+                stack.push((import_value, import_successors, 0));
+            }
+        } else {
+            postorder.push(package_name.clone());
+            stack.pop();
+        }
+    }
+
+    let index: HashMap<&str, usize> = postorder
+        .iter()
+        .enumerate()
+        .map(|(position, package_name)| (package_name.as_str(), position))
+        .collect();
+
+    let mut predecessors = vec![Vec::<usize>::new(); postorder.len()];
+
+    for (position, package_name) in postorder.iter().enumerate() {
+        for import_value in successors(package_name) {
+            if let Some(&import_position) = index.get(import_value.as_str()) {
+                predecessors[import_position].push(position);
+            }
+        }
+    }
+
+    // iterative dominator computation (Cooper, Harvey & Kennedy)
+    let root_position = postorder.len() - 1;
+    let mut idom: Vec<Option<usize>> = vec![None; postorder.len()];
+    idom[root_position] = Some(root_position);
+
+    let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while a < b {
+                a = idom[a].unwrap();
+            }
+            while b < a {
+                b = idom[b].unwrap();
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+
+    while changed {
+        changed = false;
+
+        for position in (0..root_position).rev() {
+            let mut new_idom = None;
+
+            for &predecessor in &predecessors[position] {
+                if idom[predecessor].is_none() {
+                    continue;
+                }
+
+                new_idom = match new_idom {
+                    None => Some(predecessor),
+                    Some(current) => Some(intersect(&idom, predecessor, current)),
+                };
+            }
+
+            if new_idom.is_some() && idom[position] != new_idom {
+                idom[position] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    let dominator_tree = DashMap::<String, Vec<String>>::new();
+
+    for (position, package_name) in postorder.iter().enumerate().rev() {
+        dominator_tree.entry(package_name.clone()).or_default();
+
+        if position != root_position {
+            if let Some(dominator) = idom[position] {
+                dominator_tree
+                    .entry(postorder[dominator].clone())
+                    .or_default()
+                    .push(package_name.clone());
+            }
+        }
+    }
+
+    Some(dominator_tree)
+}
+
+// Function to count how many packages each package dominates (excluding itself)
+pub fn count_dominated(
+    dominator_tree: &DashMap<String, Vec<String>>,
+    root: &str,
+) -> HashMap<String, usize> {
+    let mut counts = HashMap::<String, usize>::new();
+
+    // children are always listed after their dominator, so a reversed
+    // preorder visits every subtree before its root
+    let mut preorder = Vec::new();
+    let mut stack = vec![root.to_string()];
+
+    while let Some(package_name) = stack.pop() {
+        if let Some(children) = dominator_tree.get(&package_name) {
+            stack.extend(children.iter().cloned());
+        }
+        preorder.push(package_name);
+    }
+
+    for package_name in preorder.iter().rev() {
+        let dominated = dominator_tree
+            .get(package_name)
+            .map(|children| {
+                children
+                    .iter()
+                    .map(|child| counts.get(child).copied().unwrap_or(0) + 1)
+                    .sum()
+            })
+            .unwrap_or(0);
+
+        counts.insert(package_name.clone(), dominated);
+    }
+
+    counts
+}
+
+// Limits on which packages and edges the dependency tree includes
+#[derive(Debug, Clone, Default)]
+pub struct TreeOptions {
+    pub depth: Option<usize>,
+    pub node_budget: Option<usize>,
+    pub internal_only: bool,
+    pub target_prefix: Option<String>,
+    pub exclude_packages: Vec<regex::Regex>,
+    pub bridge: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::imports_map;
+    use crate::transform::sorted_edges;
+
+    #[test]
+    fn repeated_imports_become_one_edge_in_first_seen_order() {
+        let imports_map = imports_map(&[("a", &["c", "b", "c", "b", "d"])]);
+
+        let dependency_tree = build_dependency_tree(&imports_map, Some("a"), &TreeOptions::default());
+
+        assert_eq!(dependency_tree.get("a").unwrap().clone(), vec!["c", "b", "d"]);
+    }
+
+    #[test]
+    fn depth_limits_the_levels_of_edges_from_the_root() {
+        let imports_map = imports_map(&[("a", &["b"]), ("b", &["c"]), ("c", &["d"]), ("d", &[])]);
+
+        let tree_at_depth = |depth| {
+            build_dependency_tree(&imports_map, Some("a"), &TreeOptions { depth: Some(depth), ..TreeOptions::default() })
+        };
+
+        let edge = |package_name: &str, import_value: &str| (package_name.to_string(), import_value.to_string());
+
+        // a depth of 0 keeps only the root itself
+        let root_only = tree_at_depth(0);
+        assert!(root_only.contains_key("a"));
+        assert_eq!(sorted_edges(root_only), vec![]);
+
+        assert_eq!(sorted_edges(tree_at_depth(1)), vec![edge("a", "b")]);
+        assert_eq!(sorted_edges(tree_at_depth(2)), vec![edge("a", "b"), edge("b", "c")]);
+    }
+
+    #[test]
+    fn internal_only_keeps_just_the_edges_to_scanned_packages() {
+        let imports_map = imports_map(&[("com.a", &["com.b.B", "java.util.List"]), ("com.b", &[])]);
+
+        let dependency_tree =
+            build_dependency_tree(&imports_map, Some("com.a"), &TreeOptions { internal_only: true, ..TreeOptions::default() });
+
+        assert_eq!(sorted_edges(dependency_tree), vec![("com.a".to_string(), "com.b.B".to_string())]);
+    }
+
+    #[test]
+    fn target_prefix_keeps_only_edges_into_the_prefix() {
+        let imports_map = imports_map(&[
+            ("com.acme.app", &["com.acme.core", "org.other.lib"]),
+            ("com.acme.core", &["org.other.lib"]),
+            ("org.other.lib", &["com.acme.core"]),
+        ]);
+
+        let dependency_tree = build_dependency_tree(
+            &imports_map,
+            Some("com.acme"),
+            &TreeOptions { target_prefix: Some("com.acme".to_string()), ..TreeOptions::default() },
+        );
+
+        assert_eq!(
+            sorted_edges(dependency_tree),
+            vec![("com.acme.app".to_string(), "com.acme.core".to_string())],
+        );
+    }
+
+    #[test]
+    fn target_prefix_stops_at_a_segment_boundary() {
+        let imports_map = imports_map(&[
+            ("com.acme.app", &["com.acme.crypto.Cipher", "com.acme.cryptography.Hash"]),
+            ("com.acme.crypto", &[]),
+            ("com.acme.cryptography", &[]),
+        ]);
+
+        let dependency_tree = build_dependency_tree(
+            &imports_map,
+            Some("com.acme"),
+            &TreeOptions { target_prefix: Some("com.acme.crypto".to_string()), ..TreeOptions::default() },
+        );
+
+        assert_eq!(
+            sorted_edges(dependency_tree),
+            vec![("com.acme.app".to_string(), "com.acme.crypto.Cipher".to_string())],
+        );
+    }
+
+    #[test]
+    fn package_named_like_the_prefix_keeps_its_own_imports() {
+        let imports_map = imports_map(&[
+            ("com.acme", &["org.lib.Util"]),
+            ("com.acme.app", &["com.acme.core"]),
+            ("com.acme.core", &[]),
+        ]);
+
+        let dependency_tree = build_dependency_tree(&imports_map, Some("com.acme"), &TreeOptions::default());
+
+        assert_eq!(
+            sorted_edges(dependency_tree),
+            vec![
+                ("com.acme".to_string(), "org.lib.Util".to_string()),
+                ("com.acme.app".to_string(), "com.acme.core".to_string()),
+            ],
+        );
+    }
+
+    #[test]
+    fn excluded_packages_are_dropped_or_bridged() {
+        let imports_map = imports_map(&[
+            ("com.app", &["com.logging", "com.core"]),
+            ("com.logging", &["com.util"]),
+            ("com.core", &[]),
+            ("com.util", &[]),
+        ]);
+
+        let excluded_tree = |bridge| {
+            build_dependency_tree(
+                &imports_map,
+                Some("com.app"),
+                &TreeOptions {
+                    exclude_packages: vec![regex::Regex::new("^com\\.logging$").unwrap()],
+                    bridge,
+                    ..TreeOptions::default()
+                },
+            )
+        };
+
+        let edge = |package_name: &str, import_value: &str| (package_name.to_string(), import_value.to_string());
+
+        assert_eq!(sorted_edges(excluded_tree(false)), vec![edge("com.app", "com.core")]);
+        assert_eq!(
+            sorted_edges(excluded_tree(true)),
+            vec![edge("com.app", "com.core"), edge("com.app", "com.util")],
+        );
+    }
+
+    #[test]
+    fn diamond_joins_are_dominated_by_the_fork_not_by_either_side() {
+        let imports_map = imports_map(&[
+            ("a", &["b", "c"]),
+            ("b", &["d"]),
+            ("c", &["d"]),
+            ("d", &["e"]),
+        ]);
+
+        let dominator_tree = build_dominator_tree(&imports_map, "a").unwrap();
+        let dominated = count_dominated(&dominator_tree, "a");
+
+        let edge = |package_name: &str, import_value: &str| (package_name.to_string(), import_value.to_string());
+
+        assert_eq!(
+            sorted_edges(dominator_tree),
+            vec![edge("a", "b"), edge("a", "c"), edge("a", "d"), edge("d", "e")],
+        );
+        assert_eq!(dominated["a"], 4);
+        assert_eq!(dominated["b"], 0);
+        assert_eq!(dominated["d"], 1);
+        assert!(build_dominator_tree(&imports_map, "missing").is_none());
+    }
+
+    #[test]
+    fn cycles_below_the_root_still_form_a_dominator_chain() {
+        let imports_map = imports_map(&[("a", &["b"]), ("b", &["c"]), ("c", &["b", "d"])]);
+
+        let dominator_tree = build_dominator_tree(&imports_map, "a").unwrap();
+        let dominated = count_dominated(&dominator_tree, "a");
+
+        let edge = |package_name: &str, import_value: &str| (package_name.to_string(), import_value.to_string());
+
+        assert_eq!(sorted_edges(dominator_tree), vec![edge("a", "b"), edge("b", "c"), edge("c", "d")]);
+        assert_eq!(
+            [dominated["a"], dominated["b"], dominated["c"], dominated["d"]],
+            [3, 2, 1, 0],
+        );
+    }
+
+    #[test]
+    fn node_budget_keeps_the_most_connected_packages_first() {
+        let imports_map = imports_map(&[
+            ("a", &["x", "hub"]),
+            ("x", &["y"]),
+            ("hub", &["z"]),
+            ("b", &["hub"]),
+            ("c", &["hub"]),
+        ]);
+
+        let tree_with_budget = |node_budget| {
+            build_dependency_tree(&imports_map, Some("a"), &TreeOptions { node_budget: Some(node_budget), ..TreeOptions::default() })
+        };
+
+        let edge = |package_name: &str, import_value: &str| (package_name.to_string(), import_value.to_string());
+
+        // the hub is imported by three packages, so it is taken before x
+        let two_nodes = tree_with_budget(2);
+        assert_eq!(two_nodes.len(), 2);
+        assert_eq!(sorted_edges(two_nodes), vec![edge("a", "hub")]);
+
+        let three_nodes = tree_with_budget(3);
+        assert_eq!(three_nodes.len(), 3);
+        assert_eq!(three_nodes.get("a").unwrap().clone(), vec!["x", "hub"]);
+        assert_eq!(sorted_edges(three_nodes), vec![edge("a", "hub"), edge("a", "x")]);
+    }
+
+    #[test]
+    fn depth_report_grows_per_level_and_stops_once_a_cycle_adds_nothing() {
+        let imports_map = imports_map(&[("a", &["b", "d"]), ("b", &["c"]), ("c", &["a"]), ("d", &[])]);
+
+        let report = depth_budget_report(&imports_map, Some("a"), &TreeOptions::default());
+
+        // (nodes, edges) for depths 0 through 3, where the edge back to the root closes the cycle
+        assert_eq!(report, vec![(1, 0), (3, 2), (4, 3), (4, 4)]);
+    }
+}
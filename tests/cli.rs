@@ -0,0 +1,115 @@
+use std::fs;
+use std::process::Command;
+
+// Function to run the jadep binary, returning its stdout and stderr
+fn run_jadep(
+    args: &[&str],
+) -> (String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_jadep"))
+        .args(args)
+        .output()
+        .expect("could not run jadep");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn quiet_run_prints_only_the_requested_output() {
+    let folder = std::env::temp_dir().join(format!("jadep-quiet-{}", std::process::id()));
+    fs::create_dir_all(folder.join("com/acme")).unwrap();
+    fs::write(folder.join("com/acme/App.java"), "package com.acme;\nimport java.util.List;\nclass App {}\n").unwrap();
+
+    let path = folder.to_str().unwrap();
+    let (_, stderr) = run_jadep(&["metrics", "-p", path]);
+    let (quiet_stdout, quiet_stderr) = run_jadep(&["--quiet", "metrics", "-p", path]);
+
+    fs::remove_dir_all(&folder).unwrap();
+
+    // a normal run reports its progress, which --quiet drops from both streams
+    assert!(stderr.contains("Found 1 packages"), "{}", stderr);
+    assert_eq!(quiet_stderr, "");
+    assert!(!quiet_stdout.contains("Found"), "{}", quiet_stdout);
+    assert_eq!(
+        quiet_stdout.lines().map(|line| line.split_whitespace().collect::<Vec<_>>()).collect::<Vec<_>>(),
+        vec![vec!["package", "fan-in", "fan-out", "instability"], vec!["com.acme", "0", "1", "1.00"]],
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn stats_json_goes_to_stdout_while_the_image_is_still_rendered() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let folder = std::env::temp_dir().join(format!("jadep-stats-json-{}", std::process::id()));
+    fs::create_dir_all(folder.join("src/com/a")).unwrap();
+    fs::create_dir_all(folder.join("src/com/b")).unwrap();
+    fs::write(folder.join("src/com/a/A.java"), "package com.a;\nimport com.b.B;\nimport java.util.List;\n").unwrap();
+    fs::write(folder.join("src/com/b/B.java"), "package com.b;\n").unwrap();
+
+    let dot_bin = folder.join("dot");
+    fs::write(&dot_bin, "#!/bin/sh\ncat > /dev/null\necho '<svg/>'\n").unwrap();
+    fs::set_permissions(&dot_bin, fs::Permissions::from_mode(0o755)).unwrap();
+    let graph_out = folder.join("graph.svg");
+
+    let (stdout, stderr) = run_jadep(&[
+        "graph",
+        "-p", folder.join("src").to_str().unwrap(),
+        "-g", graph_out.to_str().unwrap(),
+        "--dot-bin", dot_bin.to_str().unwrap(),
+        "--stats-json",
+    ]);
+    let svg_content = fs::read_to_string(&graph_out).unwrap();
+
+    fs::remove_dir_all(&folder).unwrap();
+
+    assert_eq!(stdout, "{\"packages\":2,\"edges\":2,\"roots\":2,\"external_refs\":1}\n");
+    assert!(stderr.contains("Found 2 packages"), "{}", stderr);
+    assert_eq!(svg_content, "<svg/>\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn stream_parse_writes_one_json_line_per_parsed_file() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let folder = std::env::temp_dir().join(format!("jadep-stream-parse-{}", std::process::id()));
+    fs::create_dir_all(folder.join("src/com/a")).unwrap();
+    fs::create_dir_all(folder.join("bin")).unwrap();
+    fs::write(folder.join("src/com/a/A.java"), "package com.a;\nimport com.b.B;\nimport java.util.List;\n").unwrap();
+    fs::write(folder.join("src/com/a/Other.java"), "package com.a;\n").unwrap();
+
+    // graphviz is looked up on the PATH, where a script stands in for it
+    let dot_bin = folder.join("bin/dot");
+    fs::write(&dot_bin, "#!/bin/sh\ncat > /dev/null\necho '<svg/>'\n").unwrap();
+    fs::set_permissions(&dot_bin, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", folder.join("bin").display(), std::env::var("PATH").unwrap_or_default());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jadep"))
+        .args(["graph", "-p", folder.join("src").to_str().unwrap(), "-g", folder.join("graph.svg").to_str().unwrap()])
+        .arg("--stream-parse")
+        .env("PATH", path)
+        .output()
+        .expect("could not run jadep");
+
+    fs::remove_dir_all(&folder).unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let mut parsed_files: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    parsed_files.sort_by_key(|parsed_file| parsed_file["file"].as_str().unwrap().to_string());
+
+    assert_eq!(parsed_files.len(), 2);
+    assert!(parsed_files[0]["file"].as_str().unwrap().ends_with("A.java"));
+    assert_eq!(parsed_files[0]["package"], "com.a");
+    assert_eq!(parsed_files[0]["imports"], serde_json::json!(["com.b.B", "java.util.List"]));
+    assert_eq!(parsed_files[1]["imports"], serde_json::json!([]));
+}